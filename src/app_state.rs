@@ -1,12 +1,327 @@
 use crate::gpu::info::GpuInfo;
+use crate::gpu::process::GpuProcessInfo;
+use crate::history::{History, Metric};
+use crate::ui::columns::{GpuColumn, MemoryMetric, ProcessColumn};
+use crate::ui::details::DetailPane;
+use crate::ui::filter::ProcessFilter;
+use crate::ui::grouping::{build_rows, Grouping, RowKey, TableRow};
+use crate::ui::kill::{Escalation, KillDialog};
+use ratatui::widgets::TableState;
+use std::collections::HashSet;
+use std::time::Duration;
+
+/// Identifies a row of the process table: a process on a given GPU.
+pub type ProcessKey = (usize, u32);
 
 pub struct AppState {
-    pub selected_process: usize,
+    // The selected row, kept across re-sorting and refreshes. `None` once its
+    // process has exited, until the user picks another row.
+    pub selected: Option<RowKey>,
+    // Last row of the selection, where navigation resumes after it exited
+    pub selected_row: usize,
+    // Processes marked for batch actions
+    pub marked_processes: HashSet<ProcessKey>,
+    pub filter: ProcessFilter,
+    pub grouping: Grouping,
+    // Keys of the groups whose members are hidden
+    pub collapsed_groups: HashSet<String>,
+    // Scroll offset of the process table, kept between frames
+    pub process_table: TableState,
+    // Rows that fit in the process table, as of the last frame
+    pub process_page_size: usize,
     pub selected_gpu_tab: usize,
     pub gpu_infos: Vec<GpuInfo>,
     pub error_message: Option<String>,
-    pub power_history: Vec<Vec<u64>>,
-    pub utilization_history: Vec<Vec<u64>>,
+    // Why the last collection failed, cleared by the next successful one
+    pub collection_error: Option<String>,
+    // Outcome of the last action, shown until the next one
+    pub status_message: Option<String>,
+    pub history: History,
     pub use_tabbed_graphs: bool,
     pub use_bar_charts: bool,
+    // Series shown in the left and right graph of each GPU
+    pub graph_metrics: [Metric; 2],
+    pub gpu_columns: Vec<GpuColumn>,
+    // Cursor of the GPU column picker while it is open
+    pub column_picker: Option<usize>,
+    pub memory_metric: MemoryMetric,
+    pub process_columns: Vec<ProcessColumn>,
+    pub sort_column: ProcessColumn,
+    pub sort_descending: bool,
+    pub kill_dialog: Option<KillDialog>,
+    pub detail_pane: Option<DetailPane>,
+    pub escalations: Vec<Escalation>,
+    pub escalation_timeout: Duration,
+    pub replay_status: Option<String>,
+}
+
+impl AppState {
+    pub fn new(
+        use_tabbed_graphs: bool,
+        use_bar_charts: bool,
+        history_window: Duration,
+        process_columns: Vec<ProcessColumn>,
+        escalation_timeout: Duration,
+    ) -> Self {
+        AppState {
+            selected: None,
+            selected_row: 0,
+            marked_processes: HashSet::new(),
+            filter: ProcessFilter::default(),
+            grouping: Grouping::None,
+            collapsed_groups: HashSet::new(),
+            process_table: TableState::default(),
+            process_page_size: 1,
+            selected_gpu_tab: 0,
+            gpu_infos: Vec::new(),
+            error_message: None,
+            collection_error: None,
+            status_message: None,
+            history: History::new(history_window),
+            use_tabbed_graphs,
+            use_bar_charts,
+            graph_metrics: [Metric::Power, Metric::Utilization],
+            gpu_columns: GpuColumn::DEFAULT.to_vec(),
+            column_picker: None,
+            memory_metric: MemoryMetric::Rss,
+            process_columns,
+            sort_column: ProcessColumn::GpuMemory,
+            sort_descending: true,
+            kill_dialog: None,
+            detail_pane: None,
+            escalations: Vec::new(),
+            escalation_timeout,
+            replay_status: None,
+        }
+    }
+
+    /// Replaces the current GPU state and keeps the selection on the same
+    /// process. When the selected process has exited, nothing is selected
+    /// rather than whatever process moved into its row.
+    pub fn set_gpu_infos(&mut self, gpu_infos: Vec<GpuInfo>) {
+        let had_rows = !self.table_rows().is_empty();
+        self.gpu_infos = gpu_infos;
+        // Select the first row when processes show up in an empty table
+        self.sync_selection(!had_rows);
+
+        if let Some(pane) = &mut self.detail_pane {
+            let (gpu_index, pid) = pane.key;
+            let process = self
+                .gpu_infos
+                .iter()
+                .filter(|gpu| gpu.index == gpu_index)
+                .flat_map(|gpu| &gpu.processes)
+                .find(|p| p.pid == pid);
+            match process {
+                Some(process) => pane.process = process.clone(),
+                None => pane.exited = true,
+            }
+        }
+    }
+
+    /// Changes the process filter. The selection moves to the first matching
+    /// row if the selected process is filtered out.
+    pub fn update_filter(&mut self, update: impl FnOnce(&mut ProcessFilter)) {
+        update(&mut self.filter);
+        self.sync_selection(true);
+    }
+
+    /// Drops the selection and marks of processes that are no longer shown.
+    /// With `select_first`, a missing selection falls back to the first row.
+    fn sync_selection(&mut self, select_first: bool) {
+        let keys: Vec<RowKey> = self.table_rows().iter().map(TableRow::key).collect();
+        let visible: Vec<ProcessKey> = self
+            .visible_processes()
+            .into_iter()
+            .map(|(gpu, p)| (gpu, p.pid))
+            .collect();
+        let row = self
+            .selected
+            .as_ref()
+            .and_then(|key| keys.iter().position(|k| k == key))
+            .or_else(|| (select_first && !keys.is_empty()).then_some(0));
+        self.marked_processes.retain(|key| visible.contains(key));
+
+        match row {
+            Some(row) => {
+                self.selected = Some(keys[row].clone());
+                self.selected_row = row;
+            }
+            None => self.selected = None,
+        }
+    }
+
+    /// Row of the selection in `table_rows`.
+    pub fn selected_index(&self) -> Option<usize> {
+        let key = self.selected.as_ref()?;
+        self.table_rows().iter().position(|row| &row.key() == key)
+    }
+
+    /// The selected process, or every member of the selected group.
+    pub fn selected_processes(&self) -> Vec<(usize, &GpuProcessInfo)> {
+        let Some(key) = &self.selected else {
+            return Vec::new();
+        };
+        self.table_rows()
+            .into_iter()
+            .find(|row| &row.key() == key)
+            .map(|row| row.processes())
+            .unwrap_or_default()
+    }
+
+    /// Marks the selected process, or unmarks it if it already is. On a group
+    /// this marks all of its members unless they all are marked already.
+    pub fn toggle_mark(&mut self) {
+        let keys: Vec<ProcessKey> = self
+            .selected_processes()
+            .into_iter()
+            .map(|(gpu, p)| (gpu, p.pid))
+            .collect();
+        if keys.iter().all(|key| self.marked_processes.contains(key)) {
+            for key in &keys {
+                self.marked_processes.remove(key);
+            }
+        } else {
+            self.marked_processes.extend(keys);
+        }
+    }
+
+    /// Marks every process in the table for which `matches` returns true.
+    pub fn mark_where(&mut self, matches: impl Fn(usize, &GpuProcessInfo) -> bool) {
+        let keys: Vec<ProcessKey> = self
+            .visible_processes()
+            .into_iter()
+            .filter(|&(gpu, p)| matches(gpu, p))
+            .map(|(gpu, p)| (gpu, p.pid))
+            .collect();
+        self.marked_processes.extend(keys);
+    }
+
+    /// Moves the selection by `offset` rows, clamped to the table. Without a
+    /// selection, the row the last selection was in is selected.
+    pub fn move_selection(&mut self, offset: isize) {
+        let row = match self.selected_index() {
+            Some(row) => row.saturating_add_signed(offset),
+            None => self.selected_row,
+        };
+        self.select_row(row);
+    }
+
+    /// Moves the selection by a page of the process table, up if `pages` is
+    /// negative.
+    pub fn move_selection_by_pages(&mut self, pages: isize) {
+        self.move_selection(pages.saturating_mul(self.process_page_size.max(1) as isize));
+    }
+
+    /// Selects the row `row`, or the last one if the table is shorter.
+    pub fn select_row(&mut self, row: usize) {
+        let rows = self.table_rows();
+        let Some(last) = rows.len().checked_sub(1) else {
+            return;
+        };
+        let row = row.min(last);
+        self.selected = Some(rows[row].key());
+        self.selected_row = row;
+    }
+
+    /// Groups the process table by the next kind of grouping.
+    pub fn cycle_grouping(&mut self) {
+        self.grouping = self.grouping.next();
+        self.collapsed_groups.clear();
+        self.sync_selection(true);
+    }
+
+    /// Collapses or expands the selected group. On a process in a group, its
+    /// group is collapsed and selected instead.
+    pub fn toggle_collapse(&mut self) {
+        let Some(index) = self.selected_index() else {
+            return;
+        };
+        let rows = self.table_rows();
+        // The group header is the nearest one above the selection
+        let group = rows[..=index].iter().rev().find_map(|row| match row {
+            TableRow::Group(group) => Some(group.key.clone()),
+            _ => None,
+        });
+        let in_group = matches!(
+            rows[index],
+            TableRow::Group(_)
+                | TableRow::Process {
+                    branch: Some(_),
+                    ..
+                }
+        );
+        let Some(group) = group.filter(|_| in_group) else {
+            return;
+        };
+        if !self.collapsed_groups.remove(&group) {
+            self.collapsed_groups.insert(group.clone());
+        }
+        self.selected = Some(RowKey::Group(group));
+        self.sync_selection(false);
+    }
+
+    /// Rows of the process table: the visible processes, arranged in groups
+    /// when grouping is on.
+    pub fn table_rows(&self) -> Vec<TableRow<'_>> {
+        build_rows(
+            self.visible_processes(),
+            &self.gpu_infos,
+            self.grouping,
+            |key| self.collapsed_groups.contains(key),
+        )
+    }
+
+    /// Processes that pass the filter, in the order of the process table.
+    /// Rendering and every action on the selection go through this, so they
+    /// always agree on which process a row is.
+    pub fn visible_processes(&self) -> Vec<(usize, &GpuProcessInfo)> {
+        let mut processes: Vec<(usize, &GpuProcessInfo)> = self
+            .gpu_infos
+            .iter()
+            .flat_map(|gpu| gpu.processes.iter().map(move |p| (gpu.index, p)))
+            .filter(|&(gpu, p)| self.filter.matches(gpu, p))
+            .collect();
+        processes.sort_by(|&a, &b| {
+            let ordering = self.sort_column.compare(a, b, self.memory_metric);
+            let ordering = if self.sort_descending {
+                ordering.reverse()
+            } else {
+                ordering
+            };
+            // Keep equal rows in a stable order between refreshes
+            ordering.then_with(|| (a.0, a.1.pid).cmp(&(b.0, b.1.pid)))
+        });
+        processes
+    }
+
+    /// Sorts by the next visible column, starting in the direction that puts
+    /// the most interesting rows first.
+    pub fn cycle_sort_column(&mut self) {
+        let next = match self
+            .process_columns
+            .iter()
+            .position(|&c| c == self.sort_column)
+        {
+            Some(index) => self.process_columns[(index + 1) % self.process_columns.len()],
+            None => self.process_columns[0],
+        };
+        self.sort_column = next;
+        self.sort_descending = !matches!(
+            next,
+            ProcessColumn::Gpu
+                | ProcessColumn::Pid
+                | ProcessColumn::Ppid
+                | ProcessColumn::User
+                | ProcessColumn::State
+                | ProcessColumn::Start
+                | ProcessColumn::Type
+                | ProcessColumn::Cgroup
+                | ProcessColumn::Container
+                | ProcessColumn::Pod
+                | ProcessColumn::Job
+                | ProcessColumn::Step
+                | ProcessColumn::Command
+        );
+    }
 }
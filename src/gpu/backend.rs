@@ -0,0 +1,10 @@
+use crate::gpu::info::GpuInfo;
+use std::error::Error;
+
+/// A source of GPU samples. The collector thread and the headless modes only
+/// talk to devices through this trait, so the UI can run against real hardware
+/// (NVML) or a synthetic backend on machines without an NVIDIA driver.
+pub trait GpuBackend: Send {
+    /// Collects the current state of every device visible to the backend.
+    fn collect(&mut self) -> Result<Vec<GpuInfo>, Box<dyn Error>>;
+}
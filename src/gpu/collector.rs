@@ -0,0 +1,49 @@
+use crate::gpu::backend::GpuBackend;
+use crate::session::Sample;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Result of one collection. Errors are passed as strings because the
+/// backend's error type cannot cross threads.
+pub type SampleResult = Result<Sample, String>;
+
+/// Samples a backend on a dedicated thread, so slow or hanging NVML and
+/// procfs queries never block input handling or rendering.
+pub struct Collector {
+    refresh: Sender<()>,
+}
+
+impl Collector {
+    /// Starts sampling every `interval`. `publish` is called with each sample
+    /// and stops the collector by returning `false`.
+    pub fn spawn<F>(mut backend: Box<dyn GpuBackend>, interval: Duration, mut publish: F) -> Self
+    where
+        F: FnMut(SampleResult) -> bool + Send + 'static,
+    {
+        let (refresh, refresh_requests) = mpsc::channel();
+        thread::spawn(move || loop {
+            let started = Instant::now();
+            let sample = backend
+                .collect()
+                .map(Sample::now)
+                .map_err(|e| e.to_string());
+            if !publish(sample) {
+                break;
+            }
+
+            // Sleep until the next interval, waking early on refresh requests
+            let remaining = interval.saturating_sub(started.elapsed());
+            match refresh_requests.recv_timeout(remaining) {
+                Ok(()) | Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        });
+        Collector { refresh }
+    }
+
+    /// Asks for a new sample without waiting for the rest of the interval.
+    pub fn request_refresh(&self) {
+        let _ = self.refresh.send(());
+    }
+}
@@ -1,2 +1,6 @@
+pub mod backend;
+pub mod collector;
 pub mod info;
+pub mod nvml;
 pub mod process;
+pub mod synthetic;
@@ -0,0 +1,452 @@
+use crate::gpu::backend::GpuBackend;
+use crate::gpu::info::GpuInfo;
+use crate::gpu::process::{GpuProcessInfo, ProcessType, ProcessUtilization};
+use crate::utils::container::ContainerResolver;
+use crate::utils::device_files::DeviceFileScanner;
+use crate::utils::pid_namespace::PidNamespace;
+use crate::utils::system::{get_process_info, ProcessTracker};
+use nvml_wrapper::enum_wrappers::device::{
+    Clock, PcieUtilCounter, PerformanceState, TemperatureSensor,
+};
+use nvml_wrapper::enums::device::UsedGpuMemory;
+use nvml_wrapper::error::NvmlError;
+use nvml_wrapper::struct_wrappers::device::{ProcessInfo, ProcessUtilizationSample};
+use nvml_wrapper::Nvml;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+// Upper bound of the back-off between attempts to reach a failing device.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Per-device bookkeeping used to degrade gracefully instead of failing the
+/// whole collection when a single query or device misbehaves.
+#[derive(Default)]
+struct DeviceState {
+    // Queries that returned `NotSupported` are not attempted again.
+    unsupported: HashSet<&'static str>,
+    // Consecutive refreshes in which the device could not be opened.
+    failures: u32,
+    retry_at: Option<Instant>,
+    last_error: Option<String>,
+    name: Option<String>,
+    // Minor number of the device's `/dev/nvidia<N>` file
+    minor_number: Option<u32>,
+    // Timestamp of the newest process utilization sample seen, in
+    // microseconds, so each refresh only averages samples it hasn't seen
+    last_utilization_sample: Option<u64>,
+}
+
+impl DeviceState {
+    /// Runs a single NVML query. `NotSupported` results are remembered and
+    /// reported as a missing value; any other error is recorded in `errors`
+    /// and the query is retried on the next refresh.
+    fn query<T>(
+        &mut self,
+        metric: &'static str,
+        errors: &mut Vec<String>,
+        f: impl FnOnce() -> Result<T, NvmlError>,
+    ) -> Option<T> {
+        if self.unsupported.contains(metric) {
+            return None;
+        }
+        match f() {
+            Ok(value) => Some(value),
+            Err(NvmlError::NotSupported) => {
+                self.unsupported.insert(metric);
+                None
+            }
+            Err(e) => {
+                errors.push(format!("{}: {}", metric, e));
+                None
+            }
+        }
+    }
+
+    fn record_failure(&mut self, error: String) {
+        self.failures += 1;
+        let delay = Duration::from_secs(1u64 << self.failures.min(6)).min(MAX_RETRY_DELAY);
+        self.retry_at = Some(Instant::now() + delay);
+        self.last_error = Some(error);
+    }
+}
+
+pub struct NvmlBackend {
+    nvml: Nvml,
+    devices: Vec<DeviceState>,
+    process_tracker: ProcessTracker,
+    container_resolver: ContainerResolver,
+    device_files: DeviceFileScanner,
+    pid_namespace: PidNamespace,
+}
+
+impl NvmlBackend {
+    pub fn new() -> Result<Self, NvmlError> {
+        Ok(Self {
+            nvml: Nvml::init()?,
+            devices: Vec::new(),
+            process_tracker: ProcessTracker::default(),
+            container_resolver: ContainerResolver::default(),
+            device_files: DeviceFileScanner::default(),
+            pid_namespace: PidNamespace::detect(),
+        })
+    }
+}
+
+impl GpuBackend for NvmlBackend {
+    fn collect(&mut self) -> Result<Vec<GpuInfo>, Box<dyn Error>> {
+        let device_count = self.nvml.device_count()? as usize;
+        self.devices.resize_with(device_count, DeviceState::default);
+        let mut gpu_infos = Vec::new();
+        // Process lists are turned into rows once every device was queried,
+        // as device file discovery depends on what NVML reported on all of them
+        let mut pending = Vec::new();
+
+        for (index, state) in self.devices.iter_mut().enumerate() {
+            // Devices that recently failed are only retried after a back-off
+            if state.retry_at.is_some_and(|at| Instant::now() < at) {
+                gpu_infos.push(GpuInfo::unavailable(
+                    index,
+                    state.name.clone(),
+                    state.last_error.clone().unwrap_or_default(),
+                ));
+                continue;
+            }
+
+            let device = match self.nvml.device_by_index(index as u32) {
+                Ok(device) => device,
+                Err(e) => {
+                    state.record_failure(format!("Device unavailable: {}", e));
+                    gpu_infos.push(GpuInfo::unavailable(
+                        index,
+                        state.name.clone(),
+                        state.last_error.clone().unwrap_or_default(),
+                    ));
+                    continue;
+                }
+            };
+            state.failures = 0;
+            state.retry_at = None;
+
+            let mut errors = Vec::new();
+            if let Some(name) = state.query("name", &mut errors, || device.name()) {
+                state.name = Some(name);
+            }
+            if state.minor_number.is_none() {
+                state.minor_number =
+                    state.query("minor number", &mut errors, || device.minor_number());
+            }
+            let temperature = state.query("temperature", &mut errors, || {
+                device.temperature(TemperatureSensor::Gpu)
+            });
+            let utilization_rates =
+                state.query("utilization", &mut errors, || device.utilization_rates());
+            let memory = state.query("memory", &mut errors, || device.memory_info());
+
+            // Convert mW to W
+            let power_usage = state
+                .query("power usage", &mut errors, || device.power_usage())
+                .map(|p| p / 1000);
+            let power_limit = state
+                .query("power limit", &mut errors, || device.enforced_power_limit())
+                .map(|p| p / 1000);
+            let clock_freq =
+                state.query("clock", &mut errors, || device.clock_info(Clock::Graphics));
+            let max_clock_freq = state.query("max clock", &mut errors, || {
+                device.max_clock_info(Clock::Graphics)
+            });
+            let sm_clock = state.query("SM clock", &mut errors, || device.clock_info(Clock::SM));
+            let max_sm_clock = state.query("max SM clock", &mut errors, || {
+                device.max_clock_info(Clock::SM)
+            });
+            let memory_clock = state.query("memory clock", &mut errors, || {
+                device.clock_info(Clock::Memory)
+            });
+            let max_memory_clock = state.query("max memory clock", &mut errors, || {
+                device.max_clock_info(Clock::Memory)
+            });
+            let fan_speed = state.query("fan speed", &mut errors, || device.fan_speed(0));
+            let performance_state = state
+                .query("performance state", &mut errors, || {
+                    device.performance_state()
+                })
+                .and_then(|p| match p {
+                    PerformanceState::Unknown => None,
+                    p => Some(p.as_c()),
+                });
+            let pcie_link_gen = state.query("PCIe link generation", &mut errors, || {
+                device.current_pcie_link_gen()
+            });
+            let pcie_link_width = state.query("PCIe link width", &mut errors, || {
+                device.current_pcie_link_width()
+            });
+            let pcie_rx = state.query("PCIe Rx", &mut errors, || {
+                device.pcie_throughput(PcieUtilCounter::Receive)
+            });
+            let pcie_tx = state.query("PCIe Tx", &mut errors, || {
+                device.pcie_throughput(PcieUtilCounter::Send)
+            });
+
+            let compute_processes = state.query("compute processes", &mut errors, || {
+                device.running_compute_processes()
+            });
+            let graphics_processes = state.query("graphics processes", &mut errors, || {
+                device.running_graphics_processes()
+            });
+            // NVML answers `NotFound` when no process used the GPU since the
+            // last sample, which means all of them were idle
+            let since = state.last_utilization_sample;
+            let utilization_samples = state.query("process utilization", &mut errors, || {
+                match device.process_utilization_stats(since) {
+                    Err(NvmlError::NotFound) => Ok(Vec::new()),
+                    result => result,
+                }
+            });
+            if let Some(newest) = utilization_samples
+                .iter()
+                .flatten()
+                .map(|sample| sample.timestamp)
+                .max()
+            {
+                state.last_utilization_sample = Some(newest);
+            }
+            let process_utilization = utilization_samples.map(average_utilization);
+            pending.push(PendingProcesses {
+                gpu_info: gpu_infos.len(),
+                // Minor numbers follow the device order unless NVML says otherwise
+                minor_number: state.minor_number.unwrap_or(index as u32),
+                compute_processes,
+                graphics_processes,
+                process_utilization,
+            });
+
+            state.last_error = if errors.is_empty() {
+                None
+            } else {
+                Some(errors.join("; "))
+            };
+
+            gpu_infos.push(GpuInfo {
+                index,
+                name: state.name.clone().unwrap_or_else(|| "Unknown".to_string()),
+                temperature,
+                utilization: utilization_rates.as_ref().map(|u| u.gpu),
+                memory_utilization: utilization_rates.as_ref().map(|u| u.memory),
+                memory_used: memory.as_ref().map(|m| m.used),
+                memory_total: memory.as_ref().map(|m| m.total),
+                power_usage,
+                power_limit,
+                clock_freq,
+                max_clock_freq,
+                sm_clock,
+                max_sm_clock,
+                memory_clock,
+                max_memory_clock,
+                fan_speed,
+                performance_state,
+                pcie_link_gen,
+                pcie_link_width,
+                pcie_rx,
+                pcie_tx,
+                processes: Vec::new(),
+                error: state.last_error.clone(),
+            });
+        }
+
+        // Processes NVML reported on some GPU, by their `/proc` PID. CUDA opens
+        // the device files of every visible GPU, so these would otherwise show
+        // up on all of them.
+        let reported: HashSet<u32> = pending
+            .iter()
+            .flat_map(PendingProcesses::pids)
+            .filter_map(|pid| self.pid_namespace.proc_pid(pid))
+            .collect();
+        let uses_device_files: Vec<bool> = pending
+            .iter()
+            .map(|p| p.needs_device_files(&self.pid_namespace))
+            .collect();
+        // Scanning `/proc` is skipped when NVML lists every device's processes
+        if uses_device_files.contains(&true) {
+            self.device_files.refresh();
+        }
+        for (pending, uses_device_files) in pending.into_iter().zip(uses_device_files) {
+            let device_file_users: Vec<(u32, ProcessType)> = if uses_device_files {
+                self.device_files
+                    .users(pending.minor_number)
+                    .iter()
+                    .copied()
+                    .filter(|(pid, _)| !reported.contains(pid))
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            gpu_infos[pending.gpu_info].processes = to_process_infos(
+                pending.compute_processes.unwrap_or_default(),
+                pending.graphics_processes.unwrap_or_default(),
+                &device_file_users,
+                pending.process_utilization.as_ref(),
+                &mut self.process_tracker,
+                &mut self.container_resolver,
+                &self.pid_namespace,
+            );
+        }
+
+        self.process_tracker.finish_refresh();
+        self.container_resolver.finish_refresh();
+        Ok(gpu_infos)
+    }
+}
+
+/// What NVML reported about the processes of one device, waiting to be turned
+/// into rows.
+struct PendingProcesses {
+    // Index into the collected `GpuInfo`s
+    gpu_info: usize,
+    minor_number: u32,
+    // `None` when the list couldn't be queried
+    compute_processes: Option<Vec<ProcessInfo>>,
+    graphics_processes: Option<Vec<ProcessInfo>>,
+    process_utilization: Option<HashMap<u32, ProcessUtilization>>,
+}
+
+impl PendingProcesses {
+    fn pids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.compute_processes
+            .iter()
+            .chain(&self.graphics_processes)
+            .flatten()
+            .map(|process| process.pid)
+    }
+
+    /// Whether processes are looked for through the device file: when NVML's
+    /// lists are unavailable, empty, or hold only PIDs that can't be found in
+    /// `/proc`, as inside containers or with restricted NVML permissions.
+    fn needs_device_files(&self, pid_namespace: &PidNamespace) -> bool {
+        if self.compute_processes.is_none() || self.graphics_processes.is_none() {
+            return true;
+        }
+        self.pids().all(|pid| {
+            pid_namespace
+                .proc_pid(pid)
+                .is_none_or(|proc_pid| !Path::new(&format!("/proc/{}", proc_pid)).exists())
+        })
+    }
+}
+
+/// Averages the utilization samples of each process, by host PID.
+fn average_utilization(samples: Vec<ProcessUtilizationSample>) -> HashMap<u32, ProcessUtilization> {
+    let mut totals: HashMap<u32, (ProcessUtilization, u32)> = HashMap::new();
+    for sample in samples {
+        let (total, count) = totals.entry(sample.pid).or_default();
+        total.sm += sample.sm_util;
+        total.memory += sample.mem_util;
+        total.encoder += sample.enc_util;
+        total.decoder += sample.dec_util;
+        *count += 1;
+    }
+    totals
+        .into_iter()
+        .map(|(pid, (total, count))| {
+            let average = ProcessUtilization {
+                sm: total.sm / count,
+                memory: total.memory / count,
+                encoder: total.encoder / count,
+                decoder: total.decoder / count,
+            };
+            (pid, average)
+        })
+        .collect()
+}
+
+/// Merges the compute and graphics process lists of a device. A process
+/// holding both kinds of context is listed once, as `C+G`. Processes found
+/// through the device file that NVML didn't report are added with unknown
+/// GPU memory and utilization. Processes NVML reports that can't be found in
+/// `/proc` are kept as foreign rows rather than dropped.
+fn to_process_infos(
+    compute_processes: Vec<ProcessInfo>,
+    graphics_processes: Vec<ProcessInfo>,
+    device_file_users: &[(u32, ProcessType)],
+    process_utilization: Option<&HashMap<u32, ProcessUtilization>>,
+    process_tracker: &mut ProcessTracker,
+    container_resolver: &mut ContainerResolver,
+    pid_namespace: &PidNamespace,
+) -> Vec<GpuProcessInfo> {
+    let mut merged: Vec<(u32, Option<u64>, ProcessType)> = Vec::new();
+    let tagged = compute_processes
+        .into_iter()
+        .map(|p| (p, ProcessType::Compute))
+        .chain(
+            graphics_processes
+                .into_iter()
+                .map(|p| (p, ProcessType::Graphics)),
+        );
+    for (process, process_type) in tagged {
+        let used_gpu_memory = match process.used_gpu_memory {
+            UsedGpuMemory::Used(bytes) => Some(bytes),
+            UsedGpuMemory::Unavailable => None,
+        };
+        match merged.iter_mut().find(|(pid, _, _)| *pid == process.pid) {
+            Some((_, memory, existing_type)) => {
+                *memory = (*memory).max(used_gpu_memory);
+                if *existing_type != process_type {
+                    *existing_type = ProcessType::ComputeGraphics;
+                }
+            }
+            None => merged.push((process.pid, used_gpu_memory, process_type)),
+        }
+    }
+    let mut foreign = Vec::new();
+    // NVML reports host PIDs, which are looked up by their `/proc` entry
+    let mut lookups: Vec<(u32, u32, Option<u64>, ProcessType, bool)> = Vec::new();
+    for (pid, used_gpu_memory, process_type) in merged {
+        match pid_namespace.proc_pid(pid) {
+            Some(proc_pid) => lookups.push((proc_pid, pid, used_gpu_memory, process_type, true)),
+            None => foreign.push((pid, used_gpu_memory, process_type)),
+        }
+    }
+    for &(pid, process_type) in device_file_users {
+        if !lookups
+            .iter()
+            .any(|&(proc_pid, _, _, _, _)| proc_pid == pid)
+        {
+            lookups.push((pid, pid, None, process_type, false));
+        }
+    }
+    // Processes without samples were idle since the last refresh
+    let utilization = |host_pid: u32| {
+        process_utilization.map(|samples| samples.get(&host_pid).copied().unwrap_or_default())
+    };
+
+    let mut processes = Vec::new();
+    for (pid, host_pid, used_gpu_memory, process_type, from_nvml) in lookups {
+        match get_process_info(
+            pid,
+            used_gpu_memory,
+            process_type,
+            process_tracker,
+            container_resolver,
+            pid_namespace,
+        ) {
+            Some(mut info) => {
+                if from_nvml {
+                    info.utilization = utilization(host_pid);
+                }
+                processes.push(info);
+            }
+            // Processes found through device files are gone once unreadable
+            None if from_nvml => foreign.push((host_pid, used_gpu_memory, process_type)),
+            None => {}
+        }
+    }
+    processes.extend(
+        foreign
+            .into_iter()
+            .map(|(pid, used_gpu_memory, process_type)| GpuProcessInfo {
+                utilization: utilization(pid),
+                ..GpuProcessInfo::foreign(pid, used_gpu_memory, process_type)
+            }),
+    );
+    processes
+}
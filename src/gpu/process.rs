@@ -1,9 +1,195 @@
-#[derive(Clone)]
+use serde::{Deserialize, Serialize};
+
+/// Kind of GPU context a process holds, as in the Type column of nvidia-smi.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProcessType {
+    Compute,
+    Graphics,
+    ComputeGraphics,
+}
+
+impl ProcessType {
+    pub fn label(self) -> &'static str {
+        match self {
+            ProcessType::Compute => "C",
+            ProcessType::Graphics => "G",
+            ProcessType::ComputeGraphics => "C+G",
+        }
+    }
+}
+
+/// The process a GPU process is grouped under in the tree view: the
+/// outermost job launcher among its ancestors, or else its parent.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Launcher {
+    pub pid: u32,
+    pub command: String,
+}
+
+/// Container engine that created a container, as told by its cgroup path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ContainerRuntime {
+    Docker,
+    Containerd,
+    CriO,
+    Podman,
+}
+
+impl ContainerRuntime {
+    pub fn label(self) -> &'static str {
+        match self {
+            ContainerRuntime::Docker => "docker",
+            ContainerRuntime::Containerd => "containerd",
+            ContainerRuntime::CriO => "cri-o",
+            ContainerRuntime::Podman => "podman",
+        }
+    }
+}
+
+/// Container a GPU process runs in. Names are resolved through the Docker
+/// socket and the kubelet pod directory when those are readable.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Container {
+    // Full 64 character container ID
+    pub id: String,
+    // `None` when the cgroup path doesn't name the engine, as with the
+    // cgroupfs driver of Kubernetes
+    pub runtime: Option<ContainerRuntime>,
+    pub name: Option<String>,
+    pub pod: Option<Pod>,
+}
+
+impl Container {
+    /// Name of the container, or its short ID as `docker ps` shows it.
+    pub fn display_name(&self) -> &str {
+        self.name
+            .as_deref()
+            .unwrap_or_else(|| self.id.get(..12).unwrap_or(&self.id))
+    }
+}
+
+/// Kubernetes pod a container belongs to.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pod {
+    pub uid: String,
+    pub name: Option<String>,
+    pub namespace: Option<String>,
+}
+
+impl Pod {
+    /// `namespace/name`, or the pod UID when the name is unknown.
+    pub fn display_name(&self) -> String {
+        match (&self.namespace, &self.name) {
+            (Some(namespace), Some(name)) => format!("{}/{}", namespace, name),
+            (None, Some(name)) => name.clone(),
+            _ => self.uid.clone(),
+        }
+    }
+}
+
+/// Slurm job a GPU process belongs to.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlurmJob {
+    pub job_id: u64,
+    // Step ID, or `batch`, `extern` or `interactive` for the special steps
+    pub step: Option<String>,
+    // Set for array jobs, where `job_id` is the ID of the single task
+    pub array_job_id: Option<u64>,
+    pub array_task_id: Option<u32>,
+    pub name: Option<String>,
+}
+
+impl SlurmJob {
+    /// Job ID as `squeue` shows it, `<array job>_<task>` for array jobs.
+    pub fn display_id(&self) -> String {
+        match (self.array_job_id, self.array_task_id) {
+            (Some(array_job_id), Some(task_id)) => format!("{}_{}", array_job_id, task_id),
+            _ => self.job_id.to_string(),
+        }
+    }
+}
+
+/// Share of the GPU's engines a process used since the previous refresh, in
+/// percent, from NVML's per-process utilization samples.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcessUtilization {
+    pub sm: u32,
+    pub memory: u32,
+    pub encoder: u32,
+    pub decoder: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct GpuProcessInfo {
     pub pid: u32,
-    pub used_gpu_memory: u64,
+    // PID of the process's `/proc` entry, which differs from `pid` when
+    // nviwatch runs in a nested PID namespace. `None` when it has no entry,
+    // and in recordings made before it was kept.
+    #[serde(default)]
+    pub proc_pid: Option<u32>,
+    // `None` when unknown, as for processes found through their open device
+    // files rather than reported by NVML
+    pub used_gpu_memory: Option<u64>,
+    pub process_type: Option<ProcessType>,
+    // `None` when the GPU doesn't sample per-process utilization or the
+    // process wasn't reported by NVML
+    #[serde(default)]
+    pub utilization: Option<ProcessUtilization>,
     pub username: String,
     pub command: String,
-    pub cpu_usage: f32,
+    // Measured between two samples, so None the first time a process is seen
+    pub cpu_usage: Option<f32>,
+    // Resident set size in bytes
     pub memory_usage: u64,
+    // Proportional and unique set size and swap from smaps_rollup, which is
+    // only readable for processes of the same user unless running as root
+    pub memory_pss: Option<u64>,
+    pub memory_uss: Option<u64>,
+    pub memory_swap: Option<u64>,
+    pub ppid: Option<u32>,
+    pub threads: Option<u32>,
+    // Single letter state from /proc/<pid>/stat, e.g. R, S or D
+    pub state: Option<char>,
+    pub nice: Option<i32>,
+    // Start time in milliseconds since the Unix epoch
+    pub start_time_ms: Option<u64>,
+    pub cgroup: Option<String>,
+    pub launcher: Option<Launcher>,
+    pub container: Option<Container>,
+    pub slurm_job: Option<SlurmJob>,
+    // Outside the PID namespace nviwatch runs in, so `pid` doesn't name a
+    // process that can be signalled from here
+    #[serde(default)]
+    pub foreign: bool,
+}
+
+impl GpuProcessInfo {
+    /// A process NVML reports but that can't be looked up in `/proc`, because
+    /// its PID is from the host's PID namespace.
+    pub fn foreign(pid: u32, used_gpu_memory: Option<u64>, process_type: ProcessType) -> Self {
+        GpuProcessInfo {
+            pid,
+            proc_pid: None,
+            used_gpu_memory,
+            process_type: Some(process_type),
+            utilization: None,
+            username: "N/A".to_string(),
+            command: "(in another PID namespace)".to_string(),
+            cpu_usage: None,
+            memory_usage: 0,
+            memory_pss: None,
+            memory_uss: None,
+            memory_swap: None,
+            ppid: None,
+            threads: None,
+            state: None,
+            nice: None,
+            start_time_ms: None,
+            cgroup: None,
+            launcher: None,
+            container: None,
+            slurm_job: None,
+            foreign: true,
+        }
+    }
 }
@@ -0,0 +1,263 @@
+use crate::gpu::backend::GpuBackend;
+use crate::gpu::info::GpuInfo;
+use crate::gpu::process::{GpuProcessInfo, Launcher, ProcessType, ProcessUtilization};
+use crate::session::unix_time_ms;
+use std::error::Error;
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+const MEMORY_TOTAL: u64 = 24 * 1024 * 1024 * 1024;
+const POWER_LIMIT: u32 = 300;
+const IDLE_POWER: u32 = 30;
+const MAX_CLOCK: u32 = 1980;
+const IDLE_CLOCK: u32 = 210;
+const MAX_MEMORY_CLOCK: u32 = 9501;
+const IDLE_MEMORY_CLOCK: u32 = 405;
+// PCIe Gen4 x16, in KB/s
+const PCIE_BANDWIDTH: u32 = 31_500_000;
+// Number of samples in one period of the load curve.
+const PERIOD: u64 = 120;
+// Synthetic PIDs start well above the default pid_max so they never collide
+// with real processes on the host.
+const BASE_PID: u32 = 5_000_000;
+// Parent of all synthetic processes, like a torchrun launcher
+const LAUNCHER_PID: u32 = BASE_PID - 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadCurve {
+    Sine,
+    Square,
+    Sawtooth,
+    Constant,
+}
+
+impl LoadCurve {
+    /// Load in the range 0.0..=1.0 at `tick` samples into the curve.
+    fn load_at(self, tick: u64) -> f64 {
+        let phase = (tick % PERIOD) as f64 / PERIOD as f64;
+        match self {
+            LoadCurve::Sine => 0.5 - 0.5 * (2.0 * PI * phase).cos(),
+            LoadCurve::Square => {
+                if phase < 0.5 {
+                    0.95
+                } else {
+                    0.05
+                }
+            }
+            LoadCurve::Sawtooth => phase,
+            LoadCurve::Constant => 0.75,
+        }
+    }
+}
+
+impl FromStr for LoadCurve {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sine" => Ok(LoadCurve::Sine),
+            "square" => Ok(LoadCurve::Square),
+            "sawtooth" => Ok(LoadCurve::Sawtooth),
+            "constant" => Ok(LoadCurve::Constant),
+            _ => Err(format!("Unknown load curve: {}", s)),
+        }
+    }
+}
+
+/// Deterministic backend producing fake devices and processes. Every call to
+/// `collect` advances the curves by one sample, so two runs with the same
+/// settings always produce the same sequence of values.
+pub struct SyntheticBackend {
+    gpu_count: usize,
+    processes_per_gpu: usize,
+    curve: LoadCurve,
+    tick: u64,
+    started_at_ms: u64,
+}
+
+impl SyntheticBackend {
+    pub fn new(gpu_count: usize, processes_per_gpu: usize, curve: LoadCurve) -> Self {
+        Self {
+            gpu_count,
+            processes_per_gpu,
+            curve,
+            tick: 0,
+            started_at_ms: unix_time_ms(),
+        }
+    }
+
+    fn processes(&self, gpu_index: usize, load: f64) -> Vec<GpuProcessInfo> {
+        (0..self.processes_per_gpu)
+            .map(|rank| {
+                let share = (rank + 1) as f64 / self.processes_per_gpu as f64;
+                let used_gpu_memory = (MEMORY_TOTAL as f64 * 0.8 * load * share
+                    / self.processes_per_gpu as f64) as u64;
+                // Split like GPU memory, so the shares add up to at most the device load
+                let engine_share = load * share / self.processes_per_gpu as f64;
+                let rss = 512 * 1024 * 1024 * (rank as u64 + 1);
+                let pid = BASE_PID + (gpu_index * 100 + rank) as u32;
+                GpuProcessInfo {
+                    pid,
+                    proc_pid: Some(pid),
+                    used_gpu_memory: Some(used_gpu_memory),
+                    process_type: Some(ProcessType::Compute),
+                    utilization: Some(ProcessUtilization {
+                        sm: (engine_share * 100.0) as u32,
+                        memory: (engine_share * 60.0) as u32,
+                        encoder: 0,
+                        decoder: 0,
+                    }),
+                    username: format!("demo{}", rank % 2),
+                    command: format!(
+                        "python train.py --local-rank {} --device cuda:{}",
+                        rank, gpu_index
+                    ),
+                    cpu_usage: Some((load * 100.0 * share) as f32),
+                    memory_usage: rss,
+                    // Ranks share the CUDA libraries, which make up a third of RSS
+                    memory_pss: Some(rss - rss / 3 + rss / 3 / self.processes_per_gpu as u64),
+                    memory_uss: Some(rss - rss / 3),
+                    memory_swap: Some(0),
+                    ppid: Some(LAUNCHER_PID),
+                    threads: Some(8 + rank as u32 * 4),
+                    state: Some(if load > 0.5 { 'R' } else { 'S' }),
+                    nice: Some(0),
+                    start_time_ms: Some(self.started_at_ms),
+                    cgroup: Some(format!(
+                        "/user.slice/user-100{}.slice/session-1.scope",
+                        rank % 2
+                    )),
+                    launcher: Some(Launcher {
+                        pid: LAUNCHER_PID,
+                        command: format!(
+                            "torchrun --nproc-per-node {} train.py",
+                            self.gpu_count * self.processes_per_gpu
+                        ),
+                    }),
+                    container: None,
+                    slurm_job: None,
+                    foreign: false,
+                }
+            })
+            .collect()
+    }
+}
+
+impl GpuBackend for SyntheticBackend {
+    fn collect(&mut self) -> Result<Vec<GpuInfo>, Box<dyn Error>> {
+        let gpu_infos = (0..self.gpu_count)
+            .map(|index| {
+                // Offset each device so the graphs do not move in lockstep
+                let offset = index as u64 * PERIOD / self.gpu_count.max(1) as u64;
+                let load = self.curve.load_at(self.tick + offset);
+                let processes = self.processes(index, load);
+                let clock = IDLE_CLOCK + (load * (MAX_CLOCK - IDLE_CLOCK) as f64) as u32;
+
+                GpuInfo {
+                    index,
+                    name: "NviWatch Synthetic GPU".to_string(),
+                    temperature: Some(35 + (load * 45.0) as u32),
+                    utilization: Some((load * 100.0) as u32),
+                    memory_utilization: Some((load * 60.0) as u32),
+                    memory_used: Some(processes.iter().filter_map(|p| p.used_gpu_memory).sum()),
+                    memory_total: Some(MEMORY_TOTAL),
+                    power_usage: Some(
+                        IDLE_POWER + (load * (POWER_LIMIT - IDLE_POWER) as f64) as u32,
+                    ),
+                    power_limit: Some(POWER_LIMIT),
+                    clock_freq: Some(clock),
+                    max_clock_freq: Some(MAX_CLOCK),
+                    sm_clock: Some(clock),
+                    max_sm_clock: Some(MAX_CLOCK),
+                    memory_clock: Some(if load > 0.1 {
+                        MAX_MEMORY_CLOCK
+                    } else {
+                        IDLE_MEMORY_CLOCK
+                    }),
+                    max_memory_clock: Some(MAX_MEMORY_CLOCK),
+                    fan_speed: Some(30 + (load * 60.0) as u32),
+                    // Idle GPUs drop to P8, busy ones run at P0
+                    performance_state: Some(if load > 0.1 { 0 } else { 8 }),
+                    pcie_link_gen: Some(if load > 0.1 { 4 } else { 1 }),
+                    pcie_link_width: Some(16),
+                    pcie_rx: Some((load * 0.4 * PCIE_BANDWIDTH as f64) as u32),
+                    pcie_tx: Some((load * 0.1 * PCIE_BANDWIDTH as f64) as u32),
+                    processes,
+                    error: None,
+                }
+            })
+            .collect();
+
+        self.tick += 1;
+        Ok(gpu_infos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::collector::Collector;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn collector_delivers_synthetic_samples() {
+        let backend = SyntheticBackend::new(2, 3, LoadCurve::Constant);
+        let (sender, samples) = mpsc::channel();
+        let _collector =
+            Collector::spawn(Box::new(backend), Duration::from_millis(1), move |sample| {
+                sender.send(sample).is_ok()
+            });
+
+        for _ in 0..3 {
+            let sample = samples
+                .recv_timeout(Duration::from_secs(5))
+                .expect("collector stalled")
+                .expect("synthetic collection failed");
+            assert_eq!(sample.gpus.len(), 2);
+            for gpu in &sample.gpus {
+                assert_eq!(gpu.processes.len(), 3);
+                assert_eq!(gpu.utilization, Some(75));
+                assert_eq!(
+                    gpu.memory_used,
+                    Some(gpu.processes.iter().filter_map(|p| p.used_gpu_memory).sum())
+                );
+                assert!(gpu.memory_used <= gpu.memory_total);
+            }
+        }
+    }
+
+    #[test]
+    fn same_settings_give_the_same_sequence() {
+        let mut a = SyntheticBackend::new(4, 2, LoadCurve::Sine);
+        let mut b = SyntheticBackend::new(4, 2, LoadCurve::Sine);
+        for _ in 0..PERIOD {
+            let (a, b) = (a.collect().unwrap(), b.collect().unwrap());
+            let values = |gpus: &[GpuInfo]| {
+                gpus.iter()
+                    .map(|gpu| {
+                        let memory: Vec<_> =
+                            gpu.processes.iter().map(|p| p.used_gpu_memory).collect();
+                        (gpu.utilization, gpu.power_usage, gpu.temperature, memory)
+                    })
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(values(&a), values(&b));
+        }
+    }
+
+    #[test]
+    fn load_curves_stay_in_range() {
+        for curve in [
+            LoadCurve::Sine,
+            LoadCurve::Square,
+            LoadCurve::Sawtooth,
+            LoadCurve::Constant,
+        ] {
+            for tick in 0..2 * PERIOD {
+                let load = curve.load_at(tick);
+                assert!((0.0..=1.0).contains(&load), "{:?} at {}", curve, tick);
+            }
+        }
+    }
+}
@@ -0,0 +1,416 @@
+use crate::app_state::ProcessKey;
+use crate::gpu::info::GpuInfo;
+use crate::gpu::process::ProcessUtilization;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::Duration;
+
+/// Resolution and retention of each history tier. Samples are kept as
+/// collected for the first tier and averaged into fixed buckets for the
+/// coarser ones, so long windows stay cheap to store and draw.
+const TIERS: [(u64, u64); 3] = [
+    (0, 10 * 60 * 1000),           // every sample for 10 minutes
+    (1000, 60 * 60 * 1000),        // 1 second averages for an hour
+    (60 * 1000, 24 * 3600 * 1000), // 1 minute averages for a day
+];
+
+// Utilization samples kept per process, enough for a full-width sparkline
+const PROCESS_SAMPLES: usize = 300;
+
+/// GPU metrics kept in the history and available as graph series.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Metric {
+    Power,
+    Utilization,
+    MemoryUtilization,
+    Temperature,
+    FanSpeed,
+    Clock,
+    SmClock,
+    MemoryClock,
+    PcieRx,
+    PcieTx,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 10] = [
+        Metric::Power,
+        Metric::Utilization,
+        Metric::MemoryUtilization,
+        Metric::Temperature,
+        Metric::FanSpeed,
+        Metric::Clock,
+        Metric::SmClock,
+        Metric::MemoryClock,
+        Metric::PcieRx,
+        Metric::PcieTx,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::Power => "Power",
+            Metric::Utilization => "Utilization",
+            Metric::MemoryUtilization => "Memory Utilization",
+            Metric::Temperature => "Temperature",
+            Metric::FanSpeed => "Fan Speed",
+            Metric::Clock => "Clock",
+            Metric::SmClock => "SM Clock",
+            Metric::MemoryClock => "Memory Clock",
+            Metric::PcieRx => "PCIe Rx",
+            Metric::PcieTx => "PCIe Tx",
+        }
+    }
+
+    pub fn unit(self) -> &'static str {
+        match self {
+            Metric::Power => "W",
+            Metric::Utilization | Metric::MemoryUtilization | Metric::FanSpeed => "%",
+            Metric::Temperature => "°C",
+            Metric::Clock | Metric::SmClock | Metric::MemoryClock => "MHz",
+            Metric::PcieRx | Metric::PcieTx => "MB/s",
+        }
+    }
+
+    /// The metric following this one, used to cycle graph series.
+    pub fn next(self) -> Metric {
+        let index = Metric::ALL.iter().position(|&m| m == self).unwrap_or(0);
+        Metric::ALL[(index + 1) % Metric::ALL.len()]
+    }
+
+    /// Current value of the metric, in the unit returned by `unit`.
+    pub fn value(self, gpu_info: &GpuInfo) -> Option<f64> {
+        let value = match self {
+            Metric::Power => gpu_info.power_usage,
+            Metric::Utilization => gpu_info.utilization,
+            Metric::MemoryUtilization => gpu_info.memory_utilization,
+            Metric::Temperature => gpu_info.temperature,
+            Metric::FanSpeed => gpu_info.fan_speed,
+            Metric::Clock => gpu_info.clock_freq,
+            Metric::SmClock => gpu_info.sm_clock,
+            Metric::MemoryClock => gpu_info.memory_clock,
+            // NVML reports PCIe throughput in KB/s
+            Metric::PcieRx => return gpu_info.pcie_rx.map(|kb| kb as f64 / 1024.0),
+            Metric::PcieTx => return gpu_info.pcie_tx.map(|kb| kb as f64 / 1024.0),
+        };
+        value.map(f64::from)
+    }
+
+    /// Upper bound of the metric on this device, if it has a natural one.
+    pub fn limit(self, gpu_info: &GpuInfo) -> Option<f64> {
+        let limit = match self {
+            Metric::Power => gpu_info.power_limit,
+            Metric::Utilization | Metric::MemoryUtilization | Metric::FanSpeed => Some(100),
+            Metric::Temperature => None,
+            Metric::Clock => gpu_info.max_clock_freq,
+            Metric::SmClock => gpu_info.max_sm_clock,
+            Metric::MemoryClock => gpu_info.max_memory_clock,
+            Metric::PcieRx | Metric::PcieTx => {
+                return pcie_bandwidth(gpu_info.pcie_link_gen?, gpu_info.pcie_link_width?)
+            }
+        };
+        limit.map(f64::from)
+    }
+}
+
+/// Usable bandwidth of a PCIe link in MB/s per direction.
+fn pcie_bandwidth(generation: u32, width: u32) -> Option<f64> {
+    let per_lane = match generation {
+        1 => 250.0,
+        2 => 500.0,
+        3 => 985.0,
+        4 => 1969.0,
+        5 => 3938.0,
+        6 => 7563.0,
+        _ => return None,
+    };
+    Some(per_lane * width as f64)
+}
+
+struct Tier {
+    resolution_ms: u64,
+    retention_ms: u64,
+    points: VecDeque<(u64, f64)>,
+    // Start, sum and count of the bucket currently being averaged
+    pending: Option<(u64, f64, u32)>,
+}
+
+impl Tier {
+    fn new(resolution_ms: u64, retention_ms: u64) -> Self {
+        Tier {
+            resolution_ms,
+            retention_ms,
+            points: VecDeque::new(),
+            pending: None,
+        }
+    }
+
+    fn push(&mut self, timestamp_ms: u64, value: f64) {
+        if self.resolution_ms == 0 {
+            self.points.push_back((timestamp_ms, value));
+        } else {
+            let bucket = timestamp_ms - timestamp_ms % self.resolution_ms;
+            match self.pending {
+                Some((start, sum, count)) if start == bucket => {
+                    self.pending = Some((start, sum + value, count + 1));
+                }
+                Some((start, sum, count)) => {
+                    self.points.push_back((start, sum / count as f64));
+                    self.pending = Some((bucket, value, 1));
+                }
+                None => self.pending = Some((bucket, value, 1)),
+            }
+        }
+
+        let oldest = timestamp_ms.saturating_sub(self.retention_ms);
+        while self.points.front().is_some_and(|&(t, _)| t < oldest) {
+            self.points.pop_front();
+        }
+    }
+
+    fn points(&self) -> impl Iterator<Item = (u64, f64)> + '_ {
+        let pending = self
+            .pending
+            .map(|(start, sum, count)| (start, sum / count as f64));
+        self.points.iter().copied().chain(pending)
+    }
+}
+
+/// Timestamped values of one metric of one GPU.
+pub struct Series {
+    tiers: Vec<Tier>,
+}
+
+impl Series {
+    fn new(window_ms: u64) -> Self {
+        let mut tiers = Vec::new();
+        for (index, &(resolution_ms, retention_ms)) in TIERS.iter().enumerate() {
+            let last = index == TIERS.len() - 1 || retention_ms >= window_ms;
+            // The coarsest tier needed always covers the whole window
+            let retention_ms = if last {
+                window_ms
+            } else {
+                retention_ms.min(window_ms)
+            };
+            tiers.push(Tier::new(resolution_ms, retention_ms));
+            if last {
+                break;
+            }
+        }
+        Series { tiers }
+    }
+
+    fn push(&mut self, timestamp_ms: u64, value: f64) {
+        for tier in &mut self.tiers {
+            tier.push(timestamp_ms, value);
+        }
+    }
+
+    /// Points between `start_ms` and `end_ms`, taken from the finest tier
+    /// that still covers the range.
+    pub fn points(&self, start_ms: u64, end_ms: u64) -> Vec<(u64, f64)> {
+        let span = end_ms.saturating_sub(start_ms);
+        let tier = self
+            .tiers
+            .iter()
+            .find(|tier| tier.retention_ms >= span)
+            .or(self.tiers.last());
+        tier.map(|tier| {
+            tier.points()
+                .filter(|&(t, _)| t >= start_ms && t <= end_ms)
+                .collect()
+        })
+        .unwrap_or_default()
+    }
+}
+
+/// Time based history of the graphed GPU metrics, along with the recent
+/// utilization of each process for the detail pane.
+pub struct History {
+    window_ms: u64,
+    latest_ms: Option<u64>,
+    series: HashMap<(usize, Metric), Series>,
+    processes: HashMap<ProcessKey, VecDeque<ProcessUtilization>>,
+}
+
+impl History {
+    pub fn new(window: Duration) -> Self {
+        History {
+            window_ms: u64::try_from(window.as_millis())
+                .unwrap_or(u64::MAX)
+                .max(1000),
+            latest_ms: None,
+            series: HashMap::new(),
+            processes: HashMap::new(),
+        }
+    }
+
+    pub fn window_ms(&self) -> u64 {
+        self.window_ms
+    }
+
+    /// Timestamp of the newest sample, the right edge of the graphs.
+    pub fn latest_ms(&self) -> Option<u64> {
+        self.latest_ms
+    }
+
+    pub fn clear(&mut self) {
+        self.latest_ms = None;
+        self.series.clear();
+        self.processes.clear();
+    }
+
+    pub fn record(&mut self, timestamp_ms: u64, gpu_infos: &[GpuInfo]) {
+        self.latest_ms = Some(self.latest_ms.map_or(timestamp_ms, |t| t.max(timestamp_ms)));
+        for gpu_info in gpu_infos {
+            for metric in Metric::ALL {
+                // Missing values are left out and show up as gaps
+                if let Some(value) = metric.value(gpu_info) {
+                    let window_ms = self.window_ms;
+                    self.series
+                        .entry((gpu_info.index, metric))
+                        .or_insert_with(|| Series::new(window_ms))
+                        .push(timestamp_ms, value);
+                }
+            }
+        }
+
+        // Samples of processes that are gone are dropped with them
+        let mut seen = HashSet::new();
+        for gpu_info in gpu_infos {
+            for process in &gpu_info.processes {
+                let Some(utilization) = process.utilization else {
+                    continue;
+                };
+                let key = (gpu_info.index, process.pid);
+                let samples = self.processes.entry(key).or_default();
+                if samples.len() == PROCESS_SAMPLES {
+                    samples.pop_front();
+                }
+                samples.push_back(utilization);
+                seen.insert(key);
+            }
+        }
+        self.processes.retain(|key, _| seen.contains(key));
+    }
+
+    pub fn series(&self, gpu_index: usize, metric: Metric) -> Option<&Series> {
+        self.series.get(&(gpu_index, metric))
+    }
+
+    /// Recent utilization samples of a process, oldest first.
+    pub fn process_utilization(&self, key: ProcessKey) -> Option<&VecDeque<ProcessUtilization>> {
+        self.processes.get(&key)
+    }
+}
+
+/// Parses a history window such as `90s`, `10m`, `2h` or `1d`. A number
+/// without a unit is taken as seconds.
+pub fn parse_window(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("Invalid history window: {}", value))?;
+    let multiplier = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return Err(format!("Invalid history window unit: {}", unit)),
+    };
+    // The history keeps timestamps in milliseconds, so the window must fit
+    // in them as well
+    let seconds = number
+        .checked_mul(multiplier)
+        .filter(|seconds| seconds.checked_mul(1000).is_some())
+        .ok_or_else(|| format!("Invalid history window: {}", value))?;
+    if seconds == 0 {
+        return Err("History window must be longer than zero".to_string());
+    }
+    Ok(Duration::from_secs(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_units() {
+        assert_eq!(parse_window("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_window("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_window("10m"), Ok(Duration::from_secs(600)));
+        assert_eq!(parse_window("2h"), Ok(Duration::from_secs(7200)));
+        assert_eq!(parse_window(" 1d "), Ok(Duration::from_secs(86400)));
+        assert!(parse_window("5w").is_err());
+        assert!(parse_window("m").is_err());
+        assert!(parse_window("-5m").is_err());
+    }
+
+    #[test]
+    fn zero_window_is_rejected() {
+        assert!(parse_window("0").is_err());
+        assert!(parse_window("0h").is_err());
+    }
+
+    #[test]
+    fn window_must_fit_in_milliseconds() {
+        let max_seconds = u64::MAX / 1000;
+        assert_eq!(
+            parse_window(&max_seconds.to_string()),
+            Ok(Duration::from_secs(max_seconds))
+        );
+        assert!(parse_window(&(max_seconds + 1).to_string()).is_err());
+        assert!(parse_window(&format!("{}d", u64::MAX / 86400)).is_err());
+        assert!(parse_window("99999999999999999999s").is_err());
+
+        let history = History::new(Duration::from_secs(u64::MAX));
+        assert_eq!(history.window_ms(), u64::MAX);
+    }
+
+    #[test]
+    fn samples_are_averaged_per_bucket() {
+        let mut tier = Tier::new(1000, 60 * 1000);
+        tier.push(10_000, 1.0);
+        tier.push(10_400, 3.0);
+        tier.push(10_999, 5.0);
+        tier.push(11_000, 10.0);
+        tier.push(12_500, 20.0);
+
+        let points: Vec<_> = tier.points().collect();
+        assert_eq!(points, vec![(10_000, 3.0), (11_000, 10.0), (12_000, 20.0)]);
+    }
+
+    #[test]
+    fn tier_drops_points_past_its_retention() {
+        let mut tier = Tier::new(0, 10 * 1000);
+        for t in (0..=30_000).step_by(5000) {
+            tier.push(t, t as f64);
+        }
+
+        let timestamps: Vec<_> = tier.points().map(|(t, _)| t).collect();
+        assert_eq!(timestamps, vec![20_000, 25_000, 30_000]);
+    }
+
+    #[test]
+    fn series_reads_the_finest_tier_covering_the_range() {
+        let window = 30 * 60 * 1000;
+        let mut series = Series::new(window);
+        // Only the raw and the one second tiers are needed for half an hour,
+        // and the coarser of them keeps the whole window
+        assert_eq!(series.tiers.len(), 2);
+        assert_eq!(series.tiers[1].retention_ms, window);
+
+        for t in (0..=window).step_by(250) {
+            series.push(t, 1.0);
+        }
+
+        // Five minutes fit in the raw tier, a sample every 250 ms
+        assert_eq!(series.points(window - 5 * 60 * 1000, window).len(), 1201);
+        // Half an hour only fits in the one second tier
+        let points = series.points(0, window);
+        assert_eq!(points.len(), 30 * 60 + 1);
+        assert!(points.iter().all(|&(t, v)| t % 1000 == 0 && v == 1.0));
+    }
+}
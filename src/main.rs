@@ -1,24 +1,42 @@
 mod app_state;
 mod gpu;
+mod history;
+mod output;
+mod session;
 mod ui;
 mod utils;
-extern crate nvml_wrapper as nvml;
-use crate::gpu::info::collect_gpu_info;
+use crate::gpu::backend::GpuBackend;
+use crate::gpu::collector::Collector;
+use crate::gpu::info::apply_sample;
+use crate::gpu::nvml::NvmlBackend;
+use crate::gpu::synthetic::{LoadCurve, SyntheticBackend};
+use crate::history::parse_window;
+use crate::output::prometheus::serve;
+use crate::output::snapshot::{print_snapshot, OutputFormat};
+use crate::output::stream::stream_samples;
+use crate::session::record::record;
+use crate::session::replay::replay;
+use crate::session::Sample;
+use crate::ui::columns::{parse_process_columns, ProcessColumn};
+use crate::ui::details::{handle_detail_pane_key, open_detail_pane};
+use crate::ui::events::{spawn_input_thread, AppEvent};
+use crate::ui::filter::handle_filter_key;
+use crate::ui::input::{handle_column_picker_key, handle_navigation_key};
+use crate::ui::kill::{check_escalations, handle_kill_dialog_key, open_kill_dialog};
 use crate::ui::render::ui;
-use crate::utils::system::kill_selected_process;
+use crate::ui::terminal::{restore_terminal, setup_terminal, Tui};
 use app_state::AppState;
-use clap::{Arg, Command};
-use crossterm::event::{self, Event, KeyCode};
-use crossterm::execute;
-use crossterm::terminal::{
-    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
-};
-use nvml::Nvml;
-use ratatui::backend::CrosstermBackend;
-use ratatui::Terminal;
+use clap::{Arg, ArgMatches, Command};
+use crossterm::event::{Event, KeyCode};
 use std::error::Error;
 use std::io::stdout;
-use std::time::{Duration, Instant};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+// Per-process CPU usage is measured between two samples, so `--once` takes a
+// first sample and waits this long before the one it prints
+const ONCE_CPU_INTERVAL: Duration = Duration::from_millis(500);
 fn main() -> Result<(), Box<dyn Error>> {
     let matches = Command::new("nviwatch")
         .version("0.1.0")
@@ -31,127 +49,320 @@ fn main() -> Result<(), Box<dyn Error>> {
                 .value_name("MILLISECONDS")
                 .help("Refresh interval in milliseconds")
                 .default_value("100") // Set the default value to "100"
-                .required(false),
+                .required(false)
+                .global(true),
         )
         .arg(
             Arg::new("tabbed-graphs")
                 .short('t')
                 .long("tabbed-graphs")
                 .help("Display GPU graphs in tabbed view")
-                .action(clap::ArgAction::SetTrue),
+                .action(clap::ArgAction::SetTrue)
+                .global(true),
         )
         .arg(
             Arg::new("bar-chart")
                 .short('b')
                 .long("bar-chart")
                 .help("Display GPU graphs as bar charts")
+                .action(clap::ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("history")
+                .long("history")
+                .value_name("DURATION")
+                .help("Time window of the GPU graphs, e.g. 90s, 10m, 2h or 1d")
+                .default_value("60s")
+                .value_parser(parse_window)
+                .global(true),
+        )
+        .arg(
+            Arg::new("columns")
+                .long("columns")
+                .value_name("COLUMNS")
+                .help(format!(
+                    "Comma-separated process table columns in display order, from: {}",
+                    ProcessColumn::ALL.map(|c| c.name()).join(", ")
+                ))
+                .default_value(ProcessColumn::DEFAULT)
+                .value_parser(parse_process_columns)
+                .global(true),
+        )
+        .arg(
+            Arg::new("kill-timeout")
+                .long("kill-timeout")
+                .value_name("SECONDS")
+                .help("Seconds to wait before escalating a signal to SIGKILL, when enabled in the kill dialog")
+                .default_value("10")
+                .value_parser(clap::value_parser!(u64))
+                .global(true),
+        )
+        .arg(
+            Arg::new("demo")
+                .long("demo")
+                .help("Use synthetic GPUs instead of NVML (no NVIDIA driver required)")
+                .action(clap::ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("demo-gpus")
+                .long("demo-gpus")
+                .value_name("COUNT")
+                .help("Number of synthetic GPUs in demo mode")
+                .default_value("4")
+                .value_parser(clap::value_parser!(usize))
+                .requires("demo")
+                .global(true),
+        )
+        .arg(
+            Arg::new("demo-processes")
+                .long("demo-processes")
+                .value_name("COUNT")
+                .help("Number of synthetic processes per GPU in demo mode")
+                .default_value("2")
+                .value_parser(clap::value_parser!(usize))
+                .requires("demo")
+                .global(true),
+        )
+        .arg(
+            Arg::new("demo-curve")
+                .long("demo-curve")
+                .value_name("CURVE")
+                .help("Load curve of the synthetic GPUs")
+                .default_value("sine")
+                .value_parser(["sine", "square", "sawtooth", "constant"])
+                .requires("demo")
+                .global(true),
+        )
+        .arg(
+            Arg::new("once")
+                .long("once")
+                .help("Print a single snapshot to stdout and exit")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .value_name("FORMAT")
+                .help("Output format of --once")
+                .default_value("table")
+                .value_parser(["table", "json", "csv"])
+                .requires("once"),
+        )
+        .arg(
+            Arg::new("json-stream")
+                .long("json-stream")
+                .help("Print one JSON object per refresh interval to stdout instead of starting the TUI")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("once"),
+        )
+        .subcommand(
+            Command::new("record")
+                .about("Record GPU and process samples to a file without starting the TUI")
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("FILE")
+                        .help("File to write the recording to")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("replay")
+                .about("Replay a recording made with `nviwatch record` in the TUI")
+                .arg(
+                    Arg::new("file")
+                        .value_name("FILE")
+                        .help("Recording to replay")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("serve")
+                .about("Serve GPU and process metrics for Prometheus without starting the TUI")
+                .arg(
+                    Arg::new("listen")
+                        .short('l')
+                        .long("listen")
+                        .value_name("ADDRESS")
+                        .help("Address to serve /metrics on")
+                        .default_value("127.0.0.1:9400"),
+                ),
+        )
         .get_matches();
 
     let use_tabbed_graphs = matches.get_flag("tabbed-graphs");
     let use_bar_charts = matches.get_flag("bar-chart");
 
+    let history_window = *matches
+        .get_one::<Duration>("history")
+        .expect("history has a default value");
+
+    let process_columns = matches
+        .get_one::<Vec<ProcessColumn>>("columns")
+        .expect("columns has a default value")
+        .clone();
+
+    let escalation_timeout = Duration::from_secs(
+        *matches
+            .get_one::<u64>("kill-timeout")
+            .expect("kill-timeout has a default value"),
+    );
+
     let watch_interval = matches
         .get_one::<String>("watch")
         .map(|s| s.parse().expect("Invalid number"))
         .unwrap_or(1000);
 
-    let nvml = Nvml::init()?;
-
-    let mut stdout = stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    enable_raw_mode()?;
+    match matches.subcommand() {
+        Some(("record", sub_matches)) => {
+            let output = sub_matches
+                .get_one::<String>("output")
+                .expect("output is required");
+            let mut gpu_backend = create_backend(&matches)?;
+            record(
+                gpu_backend.as_mut(),
+                Duration::from_millis(watch_interval),
+                output,
+            )
+        }
+        Some(("replay", sub_matches)) => {
+            let file = sub_matches
+                .get_one::<String>("file")
+                .expect("file is required");
+            replay(
+                file,
+                AppState::new(
+                    use_tabbed_graphs,
+                    use_bar_charts,
+                    history_window,
+                    process_columns,
+                    escalation_timeout,
+                ),
+            )
+        }
+        Some(("serve", sub_matches)) => {
+            let listen = sub_matches
+                .get_one::<String>("listen")
+                .expect("listen has a default value");
+            let mut gpu_backend = create_backend(&matches)?;
+            serve(gpu_backend.as_mut(), listen)
+        }
+        _ if matches.get_flag("once") => {
+            let format: OutputFormat = matches
+                .get_one::<String>("format")
+                .map(|s| s.parse().expect("Invalid output format"))
+                .unwrap_or(OutputFormat::Table);
+            let mut gpu_backend = create_backend(&matches)?;
+            gpu_backend.collect()?;
+            thread::sleep(ONCE_CPU_INTERVAL);
+            print_snapshot(&Sample::now(gpu_backend.collect()?), format)
+        }
+        _ if matches.get_flag("json-stream") => {
+            let mut gpu_backend = create_backend(&matches)?;
+            stream_samples(
+                gpu_backend.as_mut(),
+                Duration::from_millis(watch_interval),
+                stdout().lock(),
+            )
+        }
+        _ => {
+            let gpu_backend = create_backend(&matches)?;
+            let mut terminal = setup_terminal()?;
+            let result = run(
+                &mut terminal,
+                gpu_backend,
+                AppState::new(
+                    use_tabbed_graphs,
+                    use_bar_charts,
+                    history_window,
+                    process_columns,
+                    escalation_timeout,
+                ),
+                watch_interval,
+            );
+            restore_terminal(&mut terminal)?;
+            result
+        }
+    }
+}
 
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+fn create_backend(matches: &ArgMatches) -> Result<Box<dyn GpuBackend>, Box<dyn Error>> {
+    if matches.get_flag("demo") {
+        let curve: LoadCurve = matches
+            .get_one::<String>("demo-curve")
+            .map(|s| s.parse().expect("Invalid load curve"))
+            .unwrap_or(LoadCurve::Sine);
+        Ok(Box::new(SyntheticBackend::new(
+            *matches.get_one::<usize>("demo-gpus").unwrap_or(&4),
+            *matches.get_one::<usize>("demo-processes").unwrap_or(&2),
+            curve,
+        )))
+    } else {
+        Ok(Box::new(NvmlBackend::new()?))
+    }
+}
 
-    let mut last_update = Instant::now();
+fn run(
+    terminal: &mut Tui,
+    gpu_backend: Box<dyn GpuBackend>,
+    mut app_state: AppState,
+    watch_interval: u64,
+) -> Result<(), Box<dyn Error>> {
+    let (events, event_queue) = mpsc::channel();
+    let samples = events.clone();
+    let collector = Collector::spawn(
+        gpu_backend,
+        Duration::from_millis(watch_interval),
+        move |sample| samples.send(AppEvent::Sample(sample)).is_ok(),
+    );
+    spawn_input_thread(events);
 
-    let mut app_state = AppState {
-        selected_process: 0,
-        selected_gpu_tab: 0,
-        gpu_infos: Vec::new(),
-        error_message: None,
-        power_history: Vec::new(),
-        utilization_history: Vec::new(),
-        use_tabbed_graphs,
-        use_bar_charts,
-    };
-    loop {
-        if last_update.elapsed() >= Duration::from_millis(watch_interval) {
-            last_update = Instant::now();
-            collect_gpu_info(&nvml, &mut app_state)?;
-            terminal.draw(|f| ui(f, &app_state))?;
-        }
+    terminal.draw(|f| ui(f, &mut app_state))?;
 
-        if event::poll(Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                match key.code {
-                    KeyCode::Char('q') => break,
-                    KeyCode::Up => {
-                        if app_state.selected_process > 0 {
-                            app_state.selected_process -= 1;
-                        }
-                    }
-                    KeyCode::Down => {
-                        let total_processes: usize = app_state
-                            .gpu_infos
-                            .iter()
-                            .map(|gpu| gpu.processes.len())
-                            .sum();
-                        if app_state.selected_process < total_processes - 1 {
-                            app_state.selected_process += 1;
-                        }
-                    }
-                    KeyCode::Left => {
-                        if app_state.use_tabbed_graphs && app_state.selected_gpu_tab > 0 {
-                            app_state.selected_gpu_tab -= 1;
-                        }
-                    }
-                    KeyCode::Right => {
-                        if app_state.use_tabbed_graphs
-                            && app_state.selected_gpu_tab < app_state.gpu_infos.len() - 1
-                        {
-                            app_state.selected_gpu_tab += 1;
-                        }
-                    }
-                    KeyCode::Char('x') => match kill_selected_process(&app_state) {
-                        Ok(_) => {
-                            app_state.gpu_infos = collect_gpu_info(&nvml, &mut app_state)?;
-                        }
-                        Err(e) => {
-                            app_state.error_message = Some(e.to_string());
-                        }
-                    },
-                    KeyCode::Char('d') => {
-                        app_state.use_tabbed_graphs = false;
-                        app_state.use_bar_charts = false;
-                    }
-                    KeyCode::Char('t') => {
-                        app_state.use_tabbed_graphs = true;
-                        app_state.use_bar_charts = false;
-                    }
-                    KeyCode::Char('b') => {
-                        app_state.use_tabbed_graphs = false;
-                        app_state.use_bar_charts = true;
-                    }
-                    _ => {}
+    // Only redraw when a new sample or terminal event arrives
+    while let Ok(event) = event_queue.recv() {
+        match event {
+            AppEvent::Sample(Ok(sample)) => {
+                app_state.collection_error = None;
+                apply_sample(&mut app_state, sample);
+                if check_escalations(&mut app_state) {
+                    collector.request_refresh();
                 }
             }
+            // A failed collection is shown in the UI and the last known values
+            // stay on screen until the next successful refresh
+            AppEvent::Sample(Err(e)) => {
+                app_state.collection_error =
+                    Some(format!("Failed to collect GPU information: {}", e))
+            }
+            AppEvent::Input(Event::Key(key)) => match key.code {
+                code if app_state.kill_dialog.is_some() => {
+                    if handle_kill_dialog_key(&mut app_state, code) {
+                        collector.request_refresh();
+                    }
+                }
+                code if app_state.filter.editing => handle_filter_key(&mut app_state, code),
+                code if app_state.column_picker.is_some() => {
+                    handle_column_picker_key(&mut app_state, code)
+                }
+                code if app_state.detail_pane.is_some() => {
+                    handle_detail_pane_key(&mut app_state, code)
+                }
+                KeyCode::Char('q') => break,
+                KeyCode::Char('x') => open_kill_dialog(&mut app_state),
+                KeyCode::Enter => open_detail_pane(&mut app_state, true),
+                code => {
+                    handle_navigation_key(&mut app_state, code);
+                }
+            },
+            AppEvent::Input(_) => {}
         }
 
-        if last_update.elapsed() >= Duration::from_millis(watch_interval) {
-            last_update = Instant::now();
-            app_state.gpu_infos = collect_gpu_info(&nvml, &mut app_state)?;
-        }
-
-        terminal.draw(|f| ui(f, &app_state))?;
+        terminal.draw(|f| ui(f, &mut app_state))?;
     }
 
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-    terminal.show_cursor()?;
-
     Ok(())
 }
@@ -0,0 +1,358 @@
+use crate::gpu::backend::GpuBackend;
+use crate::gpu::info::GpuInfo;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+/// Upper bound on the request line and headers of a scrape request.
+const MAX_REQUEST_BYTES: u64 = 8 * 1024;
+/// Time a client gets to send its whole request, and to read the response.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Serves the current GPU and process metrics on `/metrics` in the Prometheus
+/// text exposition format. Devices are sampled on every scrape, so the
+/// scrape interval controls the sampling rate.
+pub fn serve(backend: &mut dyn GpuBackend, listen: &str) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind(listen)?;
+    eprintln!(
+        "Serving metrics on http://{}/metrics",
+        listener.local_addr()?
+    );
+
+    // Each connection gets its own thread so a slow client cannot hold up
+    // other scrapes, only sampling the devices is serialized
+    let backend = Mutex::new(backend);
+    thread::scope(|scope| {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let backend = &backend;
+                    scope.spawn(move || {
+                        if let Err(e) = handle_connection(stream, backend) {
+                            eprintln!("Failed to handle request: {}", e);
+                        }
+                    });
+                }
+                Err(e) => eprintln!("Failed to accept connection: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Reads from a stream until a fixed point in time, rather than resetting the
+/// timeout on every read like a plain read timeout does.
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "request was not received in time",
+            ));
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.read(buf)
+    }
+}
+
+fn handle_connection(
+    mut stream: TcpStream,
+    backend: &Mutex<&mut dyn GpuBackend>,
+) -> Result<(), Box<dyn Error>> {
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(
+        DeadlineReader {
+            stream: &stream,
+            deadline: Instant::now() + REQUEST_TIMEOUT,
+        }
+        .take(MAX_REQUEST_BYTES),
+    );
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    // Skip the headers, the request body is never used
+    let mut header = String::new();
+    loop {
+        header.clear();
+        if reader.read_line(&mut header)? == 0 {
+            let reason = if reader.get_ref().limit() == 0 {
+                "request headers too large"
+            } else {
+                "connection closed before the end of the headers"
+            };
+            return Err(reason.into());
+        }
+        if header.trim_end().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts
+        .next()
+        .unwrap_or_default()
+        .split('?')
+        .next()
+        .unwrap_or_default();
+
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/metrics") => match backend.lock().unwrap_or_else(|e| e.into_inner()).collect() {
+            Ok(gpu_infos) => ("200 OK", CONTENT_TYPE, render_metrics(&gpu_infos)),
+            Err(e) => (
+                "500 Internal Server Error",
+                "text/plain",
+                format!("Failed to collect GPU metrics: {}\n", e),
+            ),
+        },
+        ("GET", "/") => (
+            "200 OK",
+            "text/plain",
+            "NviWatch exporter. Metrics are served on /metrics\n".to_string(),
+        ),
+        _ => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()?;
+    Ok(())
+}
+
+struct Metric<'a> {
+    name: &'a str,
+    help: &'a str,
+    samples: Vec<(String, String)>,
+}
+
+impl<'a> Metric<'a> {
+    fn new(name: &'a str, help: &'a str) -> Self {
+        Metric {
+            name,
+            help,
+            samples: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, labels: &[(&str, &str)], value: impl ToString) {
+        let labels = labels
+            .iter()
+            .map(|(key, value)| format!("{}=\"{}\"", key, escape_label_value(value)))
+            .collect::<Vec<_>>()
+            .join(",");
+        self.samples.push((labels, value.to_string()));
+    }
+
+    /// Values the device did not report are left out of the exposition.
+    fn add_optional<T: ToString>(&mut self, labels: &[(&str, &str)], value: Option<T>) {
+        if let Some(value) = value {
+            self.add(labels, value);
+        }
+    }
+
+    fn write_to(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} gauge", self.name);
+        for (labels, value) in &self.samples {
+            let _ = writeln!(out, "{}{{{}}} {}", self.name, labels, value);
+        }
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+pub fn render_metrics(gpu_infos: &[GpuInfo]) -> String {
+    let mut info = Metric::new("nviwatch_gpu_info", "GPU device information.");
+    let mut up = Metric::new(
+        "nviwatch_gpu_up",
+        "Whether all queries for the GPU succeeded (1) or some failed (0).",
+    );
+    let mut temperature = Metric::new(
+        "nviwatch_gpu_temperature_celsius",
+        "GPU temperature in degrees Celsius.",
+    );
+    let mut utilization = Metric::new(
+        "nviwatch_gpu_utilization_percent",
+        "GPU utilization in percent.",
+    );
+    let mut memory_used = Metric::new(
+        "nviwatch_gpu_memory_used_bytes",
+        "GPU memory in use in bytes.",
+    );
+    let mut memory_total = Metric::new(
+        "nviwatch_gpu_memory_total_bytes",
+        "Total GPU memory in bytes.",
+    );
+    let mut power_usage = Metric::new("nviwatch_gpu_power_usage_watts", "GPU power draw in watts.");
+    let mut power_limit = Metric::new(
+        "nviwatch_gpu_power_limit_watts",
+        "Enforced GPU power limit in watts.",
+    );
+    let mut memory_utilization = Metric::new(
+        "nviwatch_gpu_memory_utilization_percent",
+        "GPU memory controller utilization in percent.",
+    );
+    let mut clock = Metric::new("nviwatch_gpu_clock_mhz", "GPU graphics clock in MHz.");
+    let mut max_clock = Metric::new(
+        "nviwatch_gpu_max_clock_mhz",
+        "Maximum GPU graphics clock in MHz.",
+    );
+    let mut sm_clock = Metric::new("nviwatch_gpu_sm_clock_mhz", "GPU SM clock in MHz.");
+    let mut max_sm_clock = Metric::new(
+        "nviwatch_gpu_max_sm_clock_mhz",
+        "Maximum GPU SM clock in MHz.",
+    );
+    let mut memory_clock = Metric::new("nviwatch_gpu_memory_clock_mhz", "GPU memory clock in MHz.");
+    let mut max_memory_clock = Metric::new(
+        "nviwatch_gpu_max_memory_clock_mhz",
+        "Maximum GPU memory clock in MHz.",
+    );
+    let mut fan_speed = Metric::new(
+        "nviwatch_gpu_fan_speed_percent",
+        "GPU fan speed in percent of its maximum.",
+    );
+    let mut performance_state = Metric::new(
+        "nviwatch_gpu_performance_state",
+        "GPU performance state, from 0 (maximum) to 15 (minimum).",
+    );
+    let mut pcie_link_gen = Metric::new(
+        "nviwatch_gpu_pcie_link_generation",
+        "Current PCIe link generation.",
+    );
+    let mut pcie_link_width = Metric::new(
+        "nviwatch_gpu_pcie_link_width",
+        "Current PCIe link width in lanes.",
+    );
+    let mut pcie_rx = Metric::new(
+        "nviwatch_gpu_pcie_rx_bytes_per_second",
+        "PCIe receive throughput in bytes per second.",
+    );
+    let mut pcie_tx = Metric::new(
+        "nviwatch_gpu_pcie_tx_bytes_per_second",
+        "PCIe transmit throughput in bytes per second.",
+    );
+    let mut process_gpu_memory = Metric::new(
+        "nviwatch_process_gpu_memory_bytes",
+        "GPU memory used by a process in bytes.",
+    );
+    let mut process_cpu = Metric::new(
+        "nviwatch_process_cpu_percent",
+        "CPU usage of a GPU process in percent.",
+    );
+    let mut process_memory = Metric::new(
+        "nviwatch_process_memory_bytes",
+        "Resident host memory of a GPU process in bytes.",
+    );
+    let mut process_pss = Metric::new(
+        "nviwatch_process_pss_bytes",
+        "Proportional set size of a GPU process in bytes.",
+    );
+    let mut process_uss = Metric::new(
+        "nviwatch_process_uss_bytes",
+        "Unique set size of a GPU process in bytes.",
+    );
+    let mut process_swap = Metric::new(
+        "nviwatch_process_swap_bytes",
+        "Swapped out memory of a GPU process in bytes.",
+    );
+
+    for gpu_info in gpu_infos {
+        let index = gpu_info.index.to_string();
+        let gpu = [("gpu", index.as_str())];
+
+        info.add(&[("gpu", &index), ("name", &gpu_info.name)], 1);
+        up.add(&gpu, u8::from(gpu_info.error.is_none()));
+        temperature.add_optional(&gpu, gpu_info.temperature);
+        utilization.add_optional(&gpu, gpu_info.utilization);
+        memory_used.add_optional(&gpu, gpu_info.memory_used);
+        memory_total.add_optional(&gpu, gpu_info.memory_total);
+        power_usage.add_optional(&gpu, gpu_info.power_usage);
+        power_limit.add_optional(&gpu, gpu_info.power_limit);
+        memory_utilization.add_optional(&gpu, gpu_info.memory_utilization);
+        clock.add_optional(&gpu, gpu_info.clock_freq);
+        max_clock.add_optional(&gpu, gpu_info.max_clock_freq);
+        sm_clock.add_optional(&gpu, gpu_info.sm_clock);
+        max_sm_clock.add_optional(&gpu, gpu_info.max_sm_clock);
+        memory_clock.add_optional(&gpu, gpu_info.memory_clock);
+        max_memory_clock.add_optional(&gpu, gpu_info.max_memory_clock);
+        fan_speed.add_optional(&gpu, gpu_info.fan_speed);
+        performance_state.add_optional(&gpu, gpu_info.performance_state);
+        pcie_link_gen.add_optional(&gpu, gpu_info.pcie_link_gen);
+        pcie_link_width.add_optional(&gpu, gpu_info.pcie_link_width);
+        // NVML reports PCIe throughput in KB/s
+        pcie_rx.add_optional(&gpu, gpu_info.pcie_rx.map(|kb| u64::from(kb) * 1024));
+        pcie_tx.add_optional(&gpu, gpu_info.pcie_tx.map(|kb| u64::from(kb) * 1024));
+
+        for process in &gpu_info.processes {
+            let pid = process.pid.to_string();
+            let labels = [
+                ("gpu", index.as_str()),
+                ("pid", pid.as_str()),
+                ("user", process.username.as_str()),
+                ("command", process.command.as_str()),
+            ];
+            process_gpu_memory.add_optional(&labels, process.used_gpu_memory);
+            process_cpu.add_optional(&labels, process.cpu_usage);
+            process_memory.add(&labels, process.memory_usage);
+            process_pss.add_optional(&labels, process.memory_pss);
+            process_uss.add_optional(&labels, process.memory_uss);
+            process_swap.add_optional(&labels, process.memory_swap);
+        }
+    }
+
+    let mut out = String::new();
+    for metric in [
+        info,
+        up,
+        temperature,
+        utilization,
+        memory_used,
+        memory_total,
+        power_usage,
+        power_limit,
+        memory_utilization,
+        clock,
+        max_clock,
+        sm_clock,
+        max_sm_clock,
+        memory_clock,
+        max_memory_clock,
+        fan_speed,
+        performance_state,
+        pcie_link_gen,
+        pcie_link_width,
+        pcie_rx,
+        pcie_tx,
+        process_gpu_memory,
+        process_cpu,
+        process_memory,
+        process_pss,
+        process_uss,
+        process_swap,
+    ] {
+        metric.write_to(&mut out);
+    }
+    out
+}
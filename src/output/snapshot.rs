@@ -0,0 +1,245 @@
+use crate::gpu::info::GpuInfo;
+use crate::gpu::process::GpuProcessInfo;
+use crate::session::Sample;
+use crate::ui::columns::{container_name, pod_name, GpuColumn};
+use crate::utils::formatting::{format_memory_size, format_or_na};
+use prettytable::{Cell, Row, Table};
+use std::error::Error;
+use std::io::{stdout, ErrorKind, Write};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err(format!("Unknown output format: {}", s)),
+        }
+    }
+}
+
+/// Prints a single sample to stdout. Tables are meant for people and use the
+/// same units as the TUI; CSV and JSON keep raw values (bytes, watts, MHz) so
+/// they can be consumed by other tools.
+pub fn print_snapshot(sample: &Sample, format: OutputFormat) -> Result<(), Box<dyn Error>> {
+    // Rendered up front so a closed pipe surfaces from a single write
+    let mut out = Vec::new();
+    match format {
+        OutputFormat::Table => {
+            gpu_table(&sample.gpus, true).print(&mut out)?;
+            let processes = process_table(&sample.gpus, true);
+            if processes.is_empty() {
+                writeln!(out, "No running GPU processes")?;
+            } else {
+                processes.print(&mut out)?;
+            }
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, sample)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Csv => {
+            // GPUs and processes are written as two CSV sections separated by
+            // an empty line
+            gpu_table(&sample.gpus, false).to_csv(&mut out)?;
+            writeln!(out)?;
+            process_table(&sample.gpus, false).to_csv(&mut out)?;
+        }
+    }
+
+    let mut stdout = stdout().lock();
+    match stdout.write_all(&out).and_then(|_| stdout.flush()) {
+        // A reader like `head` closing the pipe early is a normal end of output
+        Err(e) if e.kind() != ErrorKind::BrokenPipe => Err(Box::new(e)),
+        _ => Ok(()),
+    }
+}
+
+fn gpu_table(gpu_infos: &[GpuInfo], human: bool) -> Table {
+    let mut table = Table::new();
+    table.set_titles(if human {
+        let mut titles = vec!["GPU", "Name"];
+        titles.extend(GpuColumn::ALL.iter().map(|column| column.header()));
+        titles.push("Status");
+        Row::from(titles)
+    } else {
+        Row::from([
+            "gpu",
+            "name",
+            "temperature_c",
+            "fan_speed_percent",
+            "utilization_percent",
+            "memory_utilization_percent",
+            "memory_used_bytes",
+            "memory_total_bytes",
+            "power_usage_w",
+            "power_limit_w",
+            "performance_state",
+            "clock_mhz",
+            "max_clock_mhz",
+            "sm_clock_mhz",
+            "max_sm_clock_mhz",
+            "memory_clock_mhz",
+            "max_memory_clock_mhz",
+            "pcie_link_gen",
+            "pcie_link_width",
+            "pcie_rx_kb_per_s",
+            "pcie_tx_kb_per_s",
+            "error",
+        ])
+    });
+
+    for info in gpu_infos {
+        let cells = if human {
+            let mut cells = vec![info.index.to_string(), info.name.clone()];
+            cells.extend(GpuColumn::ALL.iter().map(|column| column.value(info)));
+            cells.push(info.error.clone().unwrap_or_else(|| "OK".to_string()));
+            cells
+        } else {
+            vec![
+                info.index.to_string(),
+                info.name.clone(),
+                csv_value(info.temperature),
+                csv_value(info.fan_speed),
+                csv_value(info.utilization),
+                csv_value(info.memory_utilization),
+                csv_value(info.memory_used),
+                csv_value(info.memory_total),
+                csv_value(info.power_usage),
+                csv_value(info.power_limit),
+                csv_value(info.performance_state),
+                csv_value(info.clock_freq),
+                csv_value(info.max_clock_freq),
+                csv_value(info.sm_clock),
+                csv_value(info.max_sm_clock),
+                csv_value(info.memory_clock),
+                csv_value(info.max_memory_clock),
+                csv_value(info.pcie_link_gen),
+                csv_value(info.pcie_link_width),
+                csv_value(info.pcie_rx),
+                csv_value(info.pcie_tx),
+                info.error.clone().unwrap_or_default(),
+            ]
+        };
+        table.add_row(Row::new(cells.iter().map(|c| Cell::new(c)).collect()));
+    }
+    table
+}
+
+// Missing values are written as empty fields
+fn csv_value<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn process_table(gpu_infos: &[GpuInfo], human: bool) -> Table {
+    let mut table = Table::new();
+    table.set_titles(if human {
+        Row::from([
+            "GPU", "PID", "Type", "GPU Mem", "SM%", "CPU", "RSS", "PSS", "USS", "Swap", "User",
+            "Command",
+        ])
+    } else {
+        Row::from([
+            "gpu",
+            "pid",
+            "ppid",
+            "type",
+            "gpu_memory_bytes",
+            "sm_percent",
+            "memory_util_percent",
+            "encoder_percent",
+            "decoder_percent",
+            "cpu_percent",
+            "memory_bytes",
+            "pss_bytes",
+            "uss_bytes",
+            "swap_bytes",
+            "threads",
+            "state",
+            "nice",
+            "start_time_ms",
+            "cgroup",
+            "launcher_pid",
+            "container_id",
+            "container",
+            "pod",
+            "slurm_job_id",
+            "slurm_step",
+            "slurm_array_job_id",
+            "slurm_array_task_id",
+            "foreign",
+            "user",
+            "command",
+        ])
+    });
+
+    let mut all_processes: Vec<(usize, &GpuProcessInfo)> = gpu_infos
+        .iter()
+        .flat_map(|gpu| gpu.processes.iter().map(move |p| (gpu.index, p)))
+        .collect();
+    all_processes.sort_by_key(|(_, process)| std::cmp::Reverse(process.used_gpu_memory));
+
+    for (gpu_index, process) in all_processes {
+        let cells = if human {
+            vec![
+                gpu_index.to_string(),
+                process.pid.to_string(),
+                format_or_na(process.process_type, |t| t.label().to_string()),
+                format_or_na(process.used_gpu_memory, format_memory_size),
+                format_or_na(process.utilization, |u| format!("{}%", u.sm)),
+                format_or_na(process.cpu_usage, |u| format!("{:.1}%", u)),
+                format_memory_size(process.memory_usage),
+                format_or_na(process.memory_pss, format_memory_size),
+                format_or_na(process.memory_uss, format_memory_size),
+                format_or_na(process.memory_swap, format_memory_size),
+                process.username.clone(),
+                process.command.clone(),
+            ]
+        } else {
+            vec![
+                gpu_index.to_string(),
+                process.pid.to_string(),
+                csv_value(process.ppid),
+                csv_value(process.process_type.map(|t| t.label())),
+                csv_value(process.used_gpu_memory),
+                csv_value(process.utilization.map(|u| u.sm)),
+                csv_value(process.utilization.map(|u| u.memory)),
+                csv_value(process.utilization.map(|u| u.encoder)),
+                csv_value(process.utilization.map(|u| u.decoder)),
+                csv_value(process.cpu_usage.map(|u| format!("{:.1}", u))),
+                process.memory_usage.to_string(),
+                csv_value(process.memory_pss),
+                csv_value(process.memory_uss),
+                csv_value(process.memory_swap),
+                csv_value(process.threads),
+                csv_value(process.state),
+                csv_value(process.nice),
+                csv_value(process.start_time_ms),
+                process.cgroup.clone().unwrap_or_default(),
+                csv_value(process.launcher.as_ref().map(|l| l.pid)),
+                csv_value(process.container.as_ref().map(|c| &c.id)),
+                csv_value(container_name(process)),
+                csv_value(pod_name(process)),
+                csv_value(process.slurm_job.as_ref().map(|j| j.job_id)),
+                csv_value(process.slurm_job.as_ref().and_then(|j| j.step.clone())),
+                csv_value(process.slurm_job.as_ref().and_then(|j| j.array_job_id)),
+                csv_value(process.slurm_job.as_ref().and_then(|j| j.array_task_id)),
+                process.foreign.to_string(),
+                process.username.clone(),
+                process.command.clone(),
+            ]
+        };
+        table.add_row(Row::new(cells.iter().map(|c| Cell::new(c)).collect()));
+    }
+    table
+}
@@ -0,0 +1,30 @@
+use crate::gpu::backend::GpuBackend;
+use crate::session::Sample;
+use std::error::Error;
+use std::io::{ErrorKind, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Samples the backend every `interval` and writes each sample as one line of
+/// JSON, flushing after every line so consumers see samples as soon as they
+/// are taken. Returns when the reader goes away (broken pipe).
+pub fn stream_samples<W: Write>(
+    backend: &mut dyn GpuBackend,
+    interval: Duration,
+    mut writer: W,
+) -> Result<(), Box<dyn Error>> {
+    loop {
+        let started = Instant::now();
+        let line = serde_json::to_string(&Sample::now(backend.collect()?))?;
+        if let Err(e) = writeln!(writer, "{}", line).and_then(|_| writer.flush()) {
+            if e.kind() == ErrorKind::BrokenPipe {
+                return Ok(());
+            }
+            return Err(Box::new(e));
+        }
+
+        if let Some(remaining) = interval.checked_sub(started.elapsed()) {
+            thread::sleep(remaining);
+        }
+    }
+}
@@ -0,0 +1,30 @@
+pub mod record;
+pub mod replay;
+
+use crate::gpu::info::GpuInfo;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// One recorded refresh: every GPU (with its processes) at a point in time.
+/// Recordings are stored as newline-delimited JSON, one `Sample` per line.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Sample {
+    pub timestamp_ms: u64,
+    pub gpus: Vec<GpuInfo>,
+}
+
+impl Sample {
+    pub fn now(gpus: Vec<GpuInfo>) -> Self {
+        Sample {
+            timestamp_ms: unix_time_ms(),
+            gpus,
+        }
+    }
+}
+
+pub fn unix_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
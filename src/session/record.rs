@@ -0,0 +1,19 @@
+use crate::gpu::backend::GpuBackend;
+use crate::output::stream::stream_samples;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+use std::time::Duration;
+
+/// Samples the backend every `interval` and appends each sample to `output`
+/// until the process is interrupted. Every line is flushed as soon as it is
+/// written so a recording stopped with Ctrl-C is still complete.
+pub fn record(
+    backend: &mut dyn GpuBackend,
+    interval: Duration,
+    output: &str,
+) -> Result<(), Box<dyn Error>> {
+    let writer = BufWriter::new(File::create(output)?);
+    eprintln!("Recording to {} (press Ctrl-C to stop)", output);
+    stream_samples(backend, interval, writer)
+}
@@ -0,0 +1,220 @@
+use crate::app_state::AppState;
+use crate::session::Sample;
+use crate::ui::details::{handle_detail_pane_key, open_detail_pane};
+use crate::ui::filter::handle_filter_key;
+use crate::ui::input::{handle_column_picker_key, handle_navigation_key};
+use crate::ui::render::ui;
+use crate::ui::terminal::{restore_terminal, setup_terminal, Tui};
+use crossterm::event::{self, Event, KeyCode};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error as IoError, ErrorKind};
+use std::time::{Duration, Instant};
+
+const SEEK_STEP_MS: u64 = 10_000;
+const MIN_SPEED: f64 = 0.25;
+const MAX_SPEED: f64 = 16.0;
+
+pub fn load_samples(path: &str) -> Result<Vec<Sample>, Box<dyn Error>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut samples = Vec::new();
+    for (line_number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let sample: Sample = serde_json::from_str(&line).map_err(|e| {
+            IoError::new(
+                ErrorKind::InvalidData,
+                format!("{}:{}: {}", path, line_number + 1, e),
+            )
+        })?;
+        samples.push(sample);
+    }
+    Ok(samples)
+}
+
+/// Plays back a recording made with `nviwatch record` through the regular
+/// renderer.
+pub fn replay(path: &str, app_state: AppState) -> Result<(), Box<dyn Error>> {
+    let samples = load_samples(path)?;
+    if samples.is_empty() {
+        return Err(Box::new(IoError::new(
+            ErrorKind::InvalidData,
+            format!("Recording {} contains no samples", path),
+        )));
+    }
+
+    let mut terminal = setup_terminal()?;
+    let result = run(&mut terminal, &samples, app_state);
+    restore_terminal(&mut terminal)?;
+    result
+}
+
+struct Player<'a> {
+    samples: &'a [Sample],
+    position: usize,
+    playing: bool,
+    speed: f64,
+    // Recording time (relative to the first sample) at `resumed_at`.
+    base_offset_ms: u64,
+    resumed_at: Instant,
+}
+
+impl<'a> Player<'a> {
+    fn new(samples: &'a [Sample]) -> Self {
+        Player {
+            samples,
+            position: 0,
+            playing: true,
+            speed: 1.0,
+            base_offset_ms: 0,
+            resumed_at: Instant::now(),
+        }
+    }
+
+    fn start_ms(&self) -> u64 {
+        self.samples[0].timestamp_ms
+    }
+
+    fn duration_ms(&self) -> u64 {
+        self.samples[self.samples.len() - 1]
+            .timestamp_ms
+            .saturating_sub(self.start_ms())
+    }
+
+    fn offset_ms(&self) -> u64 {
+        if self.playing {
+            let elapsed = self.resumed_at.elapsed().as_millis() as f64 * self.speed;
+            (self.base_offset_ms + elapsed as u64).min(self.duration_ms())
+        } else {
+            self.base_offset_ms
+        }
+    }
+
+    fn rebase(&mut self) {
+        self.base_offset_ms = self.offset_ms();
+        self.resumed_at = Instant::now();
+    }
+
+    fn toggle_playing(&mut self) {
+        self.rebase();
+        // Restart from the beginning when play is pressed at the end
+        if !self.playing && self.base_offset_ms >= self.duration_ms() {
+            self.base_offset_ms = 0;
+        }
+        self.playing = !self.playing;
+    }
+
+    fn set_speed(&mut self, speed: f64) {
+        self.rebase();
+        self.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+    }
+
+    fn seek(&mut self, offset_ms: u64, app_state: &mut AppState) {
+        self.base_offset_ms = offset_ms.min(self.duration_ms());
+        self.resumed_at = Instant::now();
+
+        let target = self.start_ms() + self.base_offset_ms;
+        self.position = self
+            .samples
+            .iter()
+            .rposition(|s| s.timestamp_ms <= target)
+            .unwrap_or(0);
+
+        // Rebuild the graphs from the samples within the history window
+        app_state.history.clear();
+        let history_start = self.samples[self.position]
+            .timestamp_ms
+            .saturating_sub(app_state.history.window_ms());
+        for sample in &self.samples[..=self.position] {
+            if sample.timestamp_ms >= history_start {
+                app_state.history.record(sample.timestamp_ms, &sample.gpus);
+            }
+        }
+        app_state.set_gpu_infos(self.samples[self.position].gpus.clone());
+    }
+
+    /// Moves forward to the sample matching the current playback time.
+    fn advance(&mut self, app_state: &mut AppState) {
+        let target = self.start_ms() + self.offset_ms();
+        let mut advanced = false;
+        while self.position + 1 < self.samples.len()
+            && self.samples[self.position + 1].timestamp_ms <= target
+        {
+            self.position += 1;
+            let sample = &self.samples[self.position];
+            app_state.history.record(sample.timestamp_ms, &sample.gpus);
+            advanced = true;
+        }
+        if advanced {
+            app_state.set_gpu_infos(self.samples[self.position].gpus.clone());
+        }
+        if self.playing && self.offset_ms() >= self.duration_ms() {
+            self.rebase();
+            self.playing = false;
+        }
+    }
+
+    fn status(&self) -> String {
+        format!(
+            "{} {}x {} / {} | space: play/pause | [ ]: seek | - +: speed | ↑↓: nav processes | enter: details | d/t/b: view mode | q: quit",
+            if self.playing { "▶" } else { "⏸" },
+            self.speed,
+            format_offset(self.offset_ms()),
+            format_offset(self.duration_ms()),
+        )
+    }
+}
+
+fn format_offset(ms: u64) -> String {
+    let seconds = ms / 1000;
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+fn run(
+    terminal: &mut Tui,
+    samples: &[Sample],
+    mut app_state: AppState,
+) -> Result<(), Box<dyn Error>> {
+    let mut player = Player::new(samples);
+    player.seek(0, &mut app_state);
+
+    loop {
+        player.advance(&mut app_state);
+        app_state.replay_status = Some(player.status());
+        terminal.draw(|f| ui(f, &mut app_state))?;
+
+        if event::poll(Duration::from_millis(50))? {
+            if let Event::Key(key) = event::read()? {
+                match key.code {
+                    code if app_state.filter.editing => handle_filter_key(&mut app_state, code),
+                    code if app_state.column_picker.is_some() => {
+                        handle_column_picker_key(&mut app_state, code)
+                    }
+                    code if app_state.detail_pane.is_some() => {
+                        handle_detail_pane_key(&mut app_state, code)
+                    }
+                    KeyCode::Char('q') => break,
+                    KeyCode::Enter => open_detail_pane(&mut app_state, false),
+                    KeyCode::Char(' ') => player.toggle_playing(),
+                    KeyCode::Char('[') => {
+                        let offset = player.offset_ms().saturating_sub(SEEK_STEP_MS);
+                        player.seek(offset, &mut app_state);
+                    }
+                    KeyCode::Char(']') => {
+                        let offset = player.offset_ms() + SEEK_STEP_MS;
+                        player.seek(offset, &mut app_state);
+                    }
+                    KeyCode::Char('-') => player.set_speed(player.speed / 2.0),
+                    KeyCode::Char('+') | KeyCode::Char('=') => player.set_speed(player.speed * 2.0),
+                    code => {
+                        handle_navigation_key(&mut app_state, code);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
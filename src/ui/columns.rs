@@ -0,0 +1,499 @@
+use crate::gpu::info::GpuInfo;
+use crate::gpu::process::GpuProcessInfo;
+use crate::utils::formatting::{
+    format_clock, format_duration, format_gpu_memory, format_memory_size, format_or_na,
+    format_pcie_link, format_pcie_throughput, format_power, format_timestamp,
+};
+use ratatui::layout::Constraint;
+use ratatui::style::Color;
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+/// Optional columns of the GPU info table. The GPU index and name are always
+/// shown; these can be toggled from the column picker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GpuColumn {
+    Temperature,
+    FanSpeed,
+    Utilization,
+    MemoryUtilization,
+    Memory,
+    Power,
+    PerformanceState,
+    Clock,
+    SmClock,
+    MemoryClock,
+    PcieLink,
+    PcieThroughput,
+}
+
+impl GpuColumn {
+    pub const ALL: [GpuColumn; 12] = [
+        GpuColumn::Temperature,
+        GpuColumn::FanSpeed,
+        GpuColumn::Utilization,
+        GpuColumn::MemoryUtilization,
+        GpuColumn::Memory,
+        GpuColumn::Power,
+        GpuColumn::PerformanceState,
+        GpuColumn::Clock,
+        GpuColumn::SmClock,
+        GpuColumn::MemoryClock,
+        GpuColumn::PcieLink,
+        GpuColumn::PcieThroughput,
+    ];
+
+    /// Columns shown on startup, matching the table before the extended
+    /// metrics were added.
+    pub const DEFAULT: [GpuColumn; 5] = [
+        GpuColumn::Temperature,
+        GpuColumn::Utilization,
+        GpuColumn::Memory,
+        GpuColumn::Power,
+        GpuColumn::Clock,
+    ];
+
+    pub fn header(self) -> &'static str {
+        match self {
+            GpuColumn::Temperature => "Temp",
+            GpuColumn::FanSpeed => "Fan",
+            GpuColumn::Utilization => "Util",
+            GpuColumn::MemoryUtilization => "Mem Util",
+            GpuColumn::Memory => "Memory",
+            GpuColumn::Power => "Power",
+            GpuColumn::PerformanceState => "P-State",
+            GpuColumn::Clock => "Clock",
+            GpuColumn::SmClock => "SM Clock",
+            GpuColumn::MemoryClock => "Mem Clock",
+            GpuColumn::PcieLink => "PCIe",
+            GpuColumn::PcieThroughput => "PCIe Rx/Tx",
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            GpuColumn::Temperature => Color::Red,
+            GpuColumn::FanSpeed => Color::LightRed,
+            GpuColumn::Utilization => Color::Magenta,
+            GpuColumn::MemoryUtilization => Color::LightMagenta,
+            GpuColumn::Memory => Color::Blue,
+            GpuColumn::Power => Color::Yellow,
+            GpuColumn::PerformanceState => Color::LightGreen,
+            GpuColumn::Clock | GpuColumn::SmClock | GpuColumn::MemoryClock => Color::LightCyan,
+            GpuColumn::PcieLink | GpuColumn::PcieThroughput => Color::LightBlue,
+        }
+    }
+
+    pub fn value(self, info: &GpuInfo) -> String {
+        match self {
+            GpuColumn::Temperature => format_or_na(info.temperature, |t| format!("{}°C", t)),
+            GpuColumn::FanSpeed => format_or_na(info.fan_speed, |f| format!("{}%", f)),
+            GpuColumn::Utilization => format_or_na(info.utilization, |u| format!("{}%", u)),
+            GpuColumn::MemoryUtilization => {
+                format_or_na(info.memory_utilization, |u| format!("{}%", u))
+            }
+            GpuColumn::Memory => format_gpu_memory(info.memory_used, info.memory_total),
+            GpuColumn::Power => format_power(info.power_usage, info.power_limit),
+            GpuColumn::PerformanceState => {
+                format_or_na(info.performance_state, |p| format!("P{}", p))
+            }
+            GpuColumn::Clock => format_clock(info.clock_freq, info.max_clock_freq),
+            GpuColumn::SmClock => format_clock(info.sm_clock, info.max_sm_clock),
+            GpuColumn::MemoryClock => format_clock(info.memory_clock, info.max_memory_clock),
+            GpuColumn::PcieLink => format_pcie_link(info.pcie_link_gen, info.pcie_link_width),
+            GpuColumn::PcieThroughput => format_pcie_throughput(info.pcie_rx, info.pcie_tx),
+        }
+    }
+}
+
+/// Host memory figure shown in the process table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryMetric {
+    Rss,
+    Pss,
+    Uss,
+    Swap,
+}
+
+impl MemoryMetric {
+    pub fn header(self) -> &'static str {
+        match self {
+            MemoryMetric::Rss => "RSS",
+            MemoryMetric::Pss => "PSS",
+            MemoryMetric::Uss => "USS",
+            MemoryMetric::Swap => "Swap",
+        }
+    }
+
+    pub fn next(self) -> MemoryMetric {
+        match self {
+            MemoryMetric::Rss => MemoryMetric::Pss,
+            MemoryMetric::Pss => MemoryMetric::Uss,
+            MemoryMetric::Uss => MemoryMetric::Swap,
+            MemoryMetric::Swap => MemoryMetric::Rss,
+        }
+    }
+
+    pub fn value(self, process: &GpuProcessInfo) -> Option<u64> {
+        match self {
+            MemoryMetric::Rss => Some(process.memory_usage),
+            MemoryMetric::Pss => process.memory_pss,
+            MemoryMetric::Uss => process.memory_uss,
+            MemoryMetric::Swap => process.memory_swap,
+        }
+    }
+}
+
+/// Columns of the process table. Which ones are shown, and in what order, is
+/// set with `--columns`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProcessColumn {
+    Gpu,
+    Pid,
+    Ppid,
+    User,
+    GpuMemory,
+    // Per-process shares of the GPU's engines
+    Sm,
+    MemoryUtilization,
+    Encoder,
+    Decoder,
+    Cpu,
+    // Host memory figure selected with the `m` key
+    Memory,
+    Rss,
+    Pss,
+    Uss,
+    Swap,
+    Threads,
+    State,
+    Nice,
+    Start,
+    Elapsed,
+    Type,
+    Cgroup,
+    Container,
+    Pod,
+    Job,
+    Step,
+    Command,
+}
+
+impl ProcessColumn {
+    pub const ALL: [ProcessColumn; 27] = [
+        ProcessColumn::Gpu,
+        ProcessColumn::Pid,
+        ProcessColumn::Ppid,
+        ProcessColumn::User,
+        ProcessColumn::GpuMemory,
+        ProcessColumn::Sm,
+        ProcessColumn::MemoryUtilization,
+        ProcessColumn::Encoder,
+        ProcessColumn::Decoder,
+        ProcessColumn::Cpu,
+        ProcessColumn::Memory,
+        ProcessColumn::Rss,
+        ProcessColumn::Pss,
+        ProcessColumn::Uss,
+        ProcessColumn::Swap,
+        ProcessColumn::Threads,
+        ProcessColumn::State,
+        ProcessColumn::Nice,
+        ProcessColumn::Start,
+        ProcessColumn::Elapsed,
+        ProcessColumn::Type,
+        ProcessColumn::Cgroup,
+        ProcessColumn::Container,
+        ProcessColumn::Pod,
+        ProcessColumn::Job,
+        ProcessColumn::Step,
+        ProcessColumn::Command,
+    ];
+
+    pub const DEFAULT: &'static str = "gpu,pid,gpu-mem,cpu,mem,user,command";
+
+    /// Name used in `--columns`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ProcessColumn::Gpu => "gpu",
+            ProcessColumn::Pid => "pid",
+            ProcessColumn::Ppid => "ppid",
+            ProcessColumn::User => "user",
+            ProcessColumn::GpuMemory => "gpu-mem",
+            ProcessColumn::Sm => "sm",
+            ProcessColumn::MemoryUtilization => "mem-util",
+            ProcessColumn::Encoder => "enc",
+            ProcessColumn::Decoder => "dec",
+            ProcessColumn::Cpu => "cpu",
+            ProcessColumn::Memory => "mem",
+            ProcessColumn::Rss => "rss",
+            ProcessColumn::Pss => "pss",
+            ProcessColumn::Uss => "uss",
+            ProcessColumn::Swap => "swap",
+            ProcessColumn::Threads => "threads",
+            ProcessColumn::State => "state",
+            ProcessColumn::Nice => "nice",
+            ProcessColumn::Start => "start",
+            ProcessColumn::Elapsed => "elapsed",
+            ProcessColumn::Type => "type",
+            ProcessColumn::Cgroup => "cgroup",
+            ProcessColumn::Container => "container",
+            ProcessColumn::Pod => "pod",
+            ProcessColumn::Job => "job",
+            ProcessColumn::Step => "step",
+            ProcessColumn::Command => "command",
+        }
+    }
+
+    pub fn header(self, memory_metric: MemoryMetric) -> &'static str {
+        match self {
+            ProcessColumn::Gpu => "GPU",
+            ProcessColumn::Pid => "PID",
+            ProcessColumn::Ppid => "PPID",
+            ProcessColumn::User => "User",
+            ProcessColumn::GpuMemory => "GPU Mem",
+            ProcessColumn::Sm => "SM%",
+            ProcessColumn::MemoryUtilization => "Mem%",
+            ProcessColumn::Encoder => "Enc%",
+            ProcessColumn::Decoder => "Dec%",
+            ProcessColumn::Cpu => "CPU",
+            ProcessColumn::Memory => memory_metric.header(),
+            ProcessColumn::Rss => "RSS",
+            ProcessColumn::Pss => "PSS",
+            ProcessColumn::Uss => "USS",
+            ProcessColumn::Swap => "Swap",
+            ProcessColumn::Threads => "Thr",
+            ProcessColumn::State => "S",
+            ProcessColumn::Nice => "NI",
+            ProcessColumn::Start => "Start",
+            ProcessColumn::Elapsed => "Elapsed",
+            ProcessColumn::Type => "Type",
+            ProcessColumn::Cgroup => "Cgroup",
+            ProcessColumn::Container => "Container",
+            ProcessColumn::Pod => "Pod",
+            ProcessColumn::Job => "Job",
+            ProcessColumn::Step => "Step",
+            ProcessColumn::Command => "Command",
+        }
+    }
+
+    pub fn width(self) -> Constraint {
+        match self {
+            ProcessColumn::Gpu => Constraint::Length(3),
+            ProcessColumn::Pid | ProcessColumn::Ppid => Constraint::Length(7),
+            ProcessColumn::User => Constraint::Length(15),
+            ProcessColumn::GpuMemory
+            | ProcessColumn::Memory
+            | ProcessColumn::Rss
+            | ProcessColumn::Pss
+            | ProcessColumn::Uss
+            | ProcessColumn::Swap => Constraint::Length(8),
+            ProcessColumn::Sm
+            | ProcessColumn::MemoryUtilization
+            | ProcessColumn::Encoder
+            | ProcessColumn::Decoder => Constraint::Length(5),
+            ProcessColumn::Cpu => Constraint::Length(7),
+            ProcessColumn::Threads => Constraint::Length(4),
+            ProcessColumn::State => Constraint::Length(2),
+            ProcessColumn::Nice => Constraint::Length(3),
+            ProcessColumn::Start => Constraint::Length(11),
+            ProcessColumn::Elapsed => Constraint::Length(11),
+            ProcessColumn::Type => Constraint::Length(4),
+            ProcessColumn::Cgroup => Constraint::Min(20),
+            ProcessColumn::Container => Constraint::Length(16),
+            ProcessColumn::Pod => Constraint::Length(24),
+            ProcessColumn::Job => Constraint::Length(12),
+            ProcessColumn::Step => Constraint::Length(11),
+            ProcessColumn::Command => Constraint::Percentage(100),
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            ProcessColumn::Gpu => Color::Cyan,
+            ProcessColumn::Pid => Color::Yellow,
+            ProcessColumn::Ppid => Color::LightYellow,
+            ProcessColumn::User => Color::Red,
+            ProcessColumn::GpuMemory => Color::Green,
+            ProcessColumn::Sm | ProcessColumn::MemoryUtilization => Color::LightMagenta,
+            ProcessColumn::Encoder | ProcessColumn::Decoder => Color::LightBlue,
+            ProcessColumn::Cpu => Color::Magenta,
+            ProcessColumn::Memory
+            | ProcessColumn::Rss
+            | ProcessColumn::Pss
+            | ProcessColumn::Uss
+            | ProcessColumn::Swap => Color::Blue,
+            ProcessColumn::Threads | ProcessColumn::State | ProcessColumn::Nice => Color::Gray,
+            ProcessColumn::Start | ProcessColumn::Elapsed => Color::LightCyan,
+            ProcessColumn::Type => Color::LightGreen,
+            ProcessColumn::Cgroup => Color::DarkGray,
+            ProcessColumn::Container | ProcessColumn::Pod => Color::LightMagenta,
+            ProcessColumn::Job | ProcessColumn::Step => Color::LightRed,
+            ProcessColumn::Command => Color::Reset,
+        }
+    }
+
+    /// Text of the column for a process. `now_ms` is the time elapsed times are
+    /// measured against, which is the sample time when replaying.
+    pub fn value(
+        self,
+        gpu_index: usize,
+        process: &GpuProcessInfo,
+        memory_metric: MemoryMetric,
+        now_ms: u64,
+    ) -> String {
+        let memory = |value: Option<u64>| format_or_na(value, format_memory_size);
+        match self {
+            ProcessColumn::Gpu => gpu_index.to_string(),
+            ProcessColumn::Pid => process.pid.to_string(),
+            ProcessColumn::Ppid => format_or_na(process.ppid, |p| p.to_string()),
+            ProcessColumn::User => process.username.clone(),
+            ProcessColumn::GpuMemory => memory(process.used_gpu_memory),
+            ProcessColumn::Sm
+            | ProcessColumn::MemoryUtilization
+            | ProcessColumn::Encoder
+            | ProcessColumn::Decoder => {
+                format_or_na(self.utilization(process), |u| format!("{}%", u))
+            }
+            ProcessColumn::Cpu => format_or_na(process.cpu_usage, |u| format!("{:.1}%", u)),
+            ProcessColumn::Memory => memory(memory_metric.value(process)),
+            ProcessColumn::Rss => memory(Some(process.memory_usage)),
+            ProcessColumn::Pss => memory(process.memory_pss),
+            ProcessColumn::Uss => memory(process.memory_uss),
+            ProcessColumn::Swap => memory(process.memory_swap),
+            ProcessColumn::Threads => format_or_na(process.threads, |t| t.to_string()),
+            ProcessColumn::State => format_or_na(process.state, |s| s.to_string()),
+            ProcessColumn::Nice => format_or_na(process.nice, |n| n.to_string()),
+            ProcessColumn::Start => format_or_na(process.start_time_ms, |start| {
+                format_timestamp(start, now_ms.saturating_sub(start))
+            }),
+            ProcessColumn::Elapsed => format_or_na(process.start_time_ms, |start| {
+                format_duration(now_ms.saturating_sub(start))
+            }),
+            ProcessColumn::Type => format_or_na(process.process_type, |t| t.label().to_string()),
+            ProcessColumn::Cgroup => process.cgroup.clone().unwrap_or_else(|| "N/A".to_string()),
+            ProcessColumn::Container => container_name(process).unwrap_or_default(),
+            ProcessColumn::Pod => pod_name(process).unwrap_or_default(),
+            ProcessColumn::Job => job_id(process).unwrap_or_default(),
+            ProcessColumn::Step => job_step(process).unwrap_or_default(),
+            ProcessColumn::Command => process.command.clone(),
+        }
+    }
+
+    /// Orders two processes by this column, ascending.
+    pub fn compare(
+        self,
+        a: (usize, &GpuProcessInfo),
+        b: (usize, &GpuProcessInfo),
+        memory_metric: MemoryMetric,
+    ) -> Ordering {
+        let ((gpu_a, a), (gpu_b, b)) = (a, b);
+        match self {
+            ProcessColumn::Gpu => gpu_a.cmp(&gpu_b),
+            ProcessColumn::Pid => a.pid.cmp(&b.pid),
+            ProcessColumn::Ppid => a.ppid.cmp(&b.ppid),
+            ProcessColumn::User => a.username.cmp(&b.username),
+            ProcessColumn::GpuMemory => a.used_gpu_memory.cmp(&b.used_gpu_memory),
+            ProcessColumn::Sm
+            | ProcessColumn::MemoryUtilization
+            | ProcessColumn::Encoder
+            | ProcessColumn::Decoder => self.utilization(a).cmp(&self.utilization(b)),
+            ProcessColumn::Cpu => match (a.cpu_usage, b.cpu_usage) {
+                (Some(a), Some(b)) => a.total_cmp(&b),
+                (a, b) => a.is_some().cmp(&b.is_some()),
+            },
+            ProcessColumn::Memory => memory_metric.value(a).cmp(&memory_metric.value(b)),
+            ProcessColumn::Rss => a.memory_usage.cmp(&b.memory_usage),
+            ProcessColumn::Pss => a.memory_pss.cmp(&b.memory_pss),
+            ProcessColumn::Uss => a.memory_uss.cmp(&b.memory_uss),
+            ProcessColumn::Swap => a.memory_swap.cmp(&b.memory_swap),
+            ProcessColumn::Threads => a.threads.cmp(&b.threads),
+            ProcessColumn::State => a.state.cmp(&b.state),
+            ProcessColumn::Nice => a.nice.cmp(&b.nice),
+            // Processes started earlier have been running longer
+            ProcessColumn::Start => a.start_time_ms.cmp(&b.start_time_ms),
+            ProcessColumn::Elapsed => b.start_time_ms.cmp(&a.start_time_ms),
+            ProcessColumn::Type => a
+                .process_type
+                .map(|t| t.label())
+                .cmp(&b.process_type.map(|t| t.label())),
+            ProcessColumn::Cgroup => a.cgroup.cmp(&b.cgroup),
+            ProcessColumn::Container => container_name(a).cmp(&container_name(b)),
+            ProcessColumn::Pod => pod_name(a).cmp(&pod_name(b)),
+            // Numerically, so job 999 comes before job 1000
+            ProcessColumn::Job => {
+                let job = |p: &GpuProcessInfo| {
+                    p.slurm_job
+                        .as_ref()
+                        .map(|job| (job.array_job_id.unwrap_or(job.job_id), job.array_task_id))
+                };
+                job(a).cmp(&job(b))
+            }
+            ProcessColumn::Step => job_step(a).cmp(&job_step(b)),
+            ProcessColumn::Command => a.command.cmp(&b.command),
+        }
+    }
+
+    /// Utilization percentage shown in the column, for the engine columns.
+    pub fn utilization(self, process: &GpuProcessInfo) -> Option<u32> {
+        let utilization = process.utilization?;
+        match self {
+            ProcessColumn::Sm => Some(utilization.sm),
+            ProcessColumn::MemoryUtilization => Some(utilization.memory),
+            ProcessColumn::Encoder => Some(utilization.encoder),
+            ProcessColumn::Decoder => Some(utilization.decoder),
+            _ => None,
+        }
+    }
+}
+
+/// Name of the container a process runs in, if any.
+pub fn container_name(process: &GpuProcessInfo) -> Option<String> {
+    let container = process.container.as_ref()?;
+    Some(container.display_name().to_string())
+}
+
+/// `namespace/name` of the pod a process runs in, if any.
+pub fn pod_name(process: &GpuProcessInfo) -> Option<String> {
+    let pod = process.container.as_ref()?.pod.as_ref()?;
+    Some(pod.display_name())
+}
+
+/// ID of the Slurm job a process belongs to, if any.
+pub fn job_id(process: &GpuProcessInfo) -> Option<String> {
+    Some(process.slurm_job.as_ref()?.display_id())
+}
+
+fn job_step(process: &GpuProcessInfo) -> Option<String> {
+    process.slurm_job.as_ref()?.step.clone()
+}
+
+impl FromStr for ProcessColumn {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ProcessColumn::ALL
+            .into_iter()
+            .find(|column| column.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<&str> = ProcessColumn::ALL.iter().map(|c| c.name()).collect();
+                format!(
+                    "Unknown column: {} (expected one of {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Parses a comma-separated list of process columns, as given to `--columns`.
+pub fn parse_process_columns(value: &str) -> Result<Vec<ProcessColumn>, String> {
+    let columns = value
+        .split(',')
+        .map(|name| name.trim().parse())
+        .collect::<Result<Vec<ProcessColumn>, String>>()?;
+    if columns.is_empty() {
+        return Err("At least one column is required".to_string());
+    }
+    Ok(columns)
+}
@@ -0,0 +1,55 @@
+use crate::app_state::{AppState, ProcessKey};
+use crate::gpu::process::GpuProcessInfo;
+use crate::ui::grouping::RowKey;
+use crate::utils::system::{read_process_details, ProcessDetails};
+use crossterm::event::KeyCode;
+
+/// State of the detail pane of a process.
+pub struct DetailPane {
+    pub key: ProcessKey,
+    // The process as last sampled, kept to show once it has exited
+    pub process: GpuProcessInfo,
+    // `None` when replaying, where there is no live process to read
+    pub details: Option<ProcessDetails>,
+    pub exited: bool,
+    pub scroll: u16,
+}
+
+/// Opens the detail pane for the selected process. `live` reads the parts
+/// that are not sampled from `/proc`, which is skipped when replaying.
+pub fn open_detail_pane(app_state: &mut AppState, live: bool) {
+    // A group header has no single process to show
+    if !matches!(app_state.selected, Some(RowKey::Process(_))) {
+        return;
+    }
+    let [(gpu_index, process)] = app_state.selected_processes()[..] else {
+        return;
+    };
+    // Read through the `/proc` entry, whose PID differs from the one shown
+    // inside a nested PID namespace
+    let details = process.proc_pid.filter(|_| live).map(read_process_details);
+    app_state.detail_pane = Some(DetailPane {
+        key: (gpu_index, process.pid),
+        process: process.clone(),
+        details,
+        exited: false,
+        scroll: 0,
+    });
+}
+
+/// Handles keys while the detail pane is open. Every key is consumed so
+/// nothing else reacts until the pane is closed.
+pub fn handle_detail_pane_key(app_state: &mut AppState, code: KeyCode) {
+    let Some(pane) = app_state.detail_pane.as_mut() else {
+        return;
+    };
+    match code {
+        KeyCode::Up | KeyCode::Char('k') => pane.scroll = pane.scroll.saturating_sub(1),
+        KeyCode::Down | KeyCode::Char('j') => pane.scroll = pane.scroll.saturating_add(1),
+        KeyCode::PageUp => pane.scroll = pane.scroll.saturating_sub(10),
+        KeyCode::PageDown => pane.scroll = pane.scroll.saturating_add(10),
+        KeyCode::Home | KeyCode::Char('g') => pane.scroll = 0,
+        KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => app_state.detail_pane = None,
+        _ => {}
+    }
+}
@@ -0,0 +1,22 @@
+use crate::gpu::collector::SampleResult;
+use crossterm::event::{self, Event};
+use std::sync::mpsc::Sender;
+use std::thread;
+
+/// Everything the main loop reacts to, delivered over a single channel so it
+/// can sleep until there is something to draw.
+pub enum AppEvent {
+    Input(Event),
+    Sample(SampleResult),
+}
+
+/// Forwards terminal events to `events` from a dedicated thread.
+pub fn spawn_input_thread(events: Sender<AppEvent>) {
+    thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            if events.send(AppEvent::Input(event)).is_err() {
+                break;
+            }
+        }
+    });
+}
@@ -0,0 +1,228 @@
+use crate::app_state::AppState;
+use crate::gpu::process::{GpuProcessInfo, ProcessType};
+use crate::ui::columns::{container_name, job_id, pod_name};
+use crossterm::event::KeyCode;
+use nix::unistd::{getuid, User};
+use regex::{Regex, RegexBuilder};
+
+/// Narrows the process table. The pattern is a regex matched against the
+/// command line, user name, PID, GPU index, container, pod and Slurm job of
+/// each process.
+#[derive(Default)]
+pub struct ProcessFilter {
+    pub pattern: String,
+    regex: Option<Regex>,
+    // Set when `pattern` is not a valid regex; the last valid one stays active
+    pub error: Option<String>,
+    // The filter prompt has keyboard focus
+    pub editing: bool,
+    // Name of the user running nviwatch when "only my processes" is on
+    only_user: Option<String>,
+    pub only_compute: bool,
+}
+
+impl ProcessFilter {
+    pub fn matches(&self, gpu_index: usize, process: &GpuProcessInfo) -> bool {
+        if let Some(user) = &self.only_user {
+            if &process.username != user {
+                return false;
+            }
+        }
+        if self.only_compute
+            && !matches!(
+                process.process_type,
+                Some(ProcessType::Compute | ProcessType::ComputeGraphics)
+            )
+        {
+            return false;
+        }
+        match &self.regex {
+            Some(regex) => {
+                regex.is_match(&process.command)
+                    || regex.is_match(&process.username)
+                    || regex.is_match(&process.pid.to_string())
+                    || regex.is_match(&gpu_index.to_string())
+                    || container_name(process).is_some_and(|name| regex.is_match(&name))
+                    || pod_name(process).is_some_and(|name| regex.is_match(&name))
+                    || job_id(process).is_some_and(|id| regex.is_match(&id))
+            }
+            None => true,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.regex.is_some() || self.only_user.is_some() || self.only_compute
+    }
+
+    pub fn only_mine(&self) -> bool {
+        self.only_user.is_some()
+    }
+
+    pub fn toggle_only_mine(&mut self) {
+        self.only_user = match self.only_user {
+            Some(_) => None,
+            None => User::from_uid(getuid())
+                .ok()
+                .flatten()
+                .map(|user| user.name),
+        };
+    }
+
+    fn set_pattern(&mut self, pattern: String) {
+        // Smart case: the pattern is case sensitive only if it has uppercase
+        let case_insensitive = !pattern.chars().any(char::is_uppercase);
+        match RegexBuilder::new(&pattern)
+            .case_insensitive(case_insensitive)
+            .build()
+        {
+            Ok(regex) => {
+                self.regex = (!pattern.is_empty()).then_some(regex);
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+        self.pattern = pattern;
+    }
+}
+
+/// Handles keys while the filter prompt is open. The table is filtered as
+/// the pattern is typed; Enter keeps the filter and Esc clears it.
+pub fn handle_filter_key(app_state: &mut AppState, code: KeyCode) {
+    match code {
+        KeyCode::Char(c) => app_state.update_filter(|filter| {
+            let mut pattern = filter.pattern.clone();
+            pattern.push(c);
+            filter.set_pattern(pattern);
+        }),
+        KeyCode::Backspace => app_state.update_filter(|filter| {
+            let mut pattern = filter.pattern.clone();
+            pattern.pop();
+            filter.set_pattern(pattern);
+        }),
+        KeyCode::Enter => app_state.filter.editing = false,
+        KeyCode::Esc => app_state.update_filter(|filter| {
+            filter.set_pattern(String::new());
+            filter.editing = false;
+        }),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::process::{Container, Pod, SlurmJob};
+
+    fn process() -> GpuProcessInfo {
+        GpuProcessInfo {
+            proc_pid: Some(4242),
+            username: "alice".to_string(),
+            command: "python Train.py --epochs 10".to_string(),
+            ..GpuProcessInfo::foreign(4242, Some(4096), ProcessType::Compute)
+        }
+    }
+
+    fn filter(pattern: &str) -> ProcessFilter {
+        let mut filter = ProcessFilter::default();
+        filter.set_pattern(pattern.to_string());
+        filter
+    }
+
+    #[test]
+    fn empty_pattern_matches_everything() {
+        let filter = filter("");
+        assert!(!filter.is_active());
+        assert!(filter.matches(0, &process()));
+    }
+
+    #[test]
+    fn pattern_is_matched_against_command_user_pid_and_gpu() {
+        let process = process();
+        assert!(filter("epochs").matches(0, &process));
+        assert!(filter("^ali").matches(0, &process));
+        assert!(filter("^4242$").matches(0, &process));
+        assert!(filter("^3$").matches(3, &process));
+        assert!(!filter("^3$").matches(1, &process));
+        assert!(!filter("bob").matches(0, &process));
+    }
+
+    #[test]
+    fn pattern_is_matched_against_container_pod_and_job() {
+        let process = GpuProcessInfo {
+            container: Some(Container {
+                id: "f".repeat(64),
+                runtime: None,
+                name: Some("trainer".to_string()),
+                pod: Some(Pod {
+                    uid: "1234".to_string(),
+                    name: Some("llm-0".to_string()),
+                    namespace: Some("research".to_string()),
+                }),
+            }),
+            slurm_job: Some(SlurmJob {
+                job_id: 1002,
+                step: None,
+                array_job_id: Some(1000),
+                array_task_id: Some(2),
+                name: None,
+            }),
+            ..process()
+        };
+        assert!(filter("^trainer$").matches(0, &process));
+        assert!(filter("^research/llm-0$").matches(0, &process));
+        assert!(filter("^1000_2$").matches(0, &process));
+        assert!(!filter("^1002$").matches(0, &process));
+    }
+
+    #[test]
+    fn pattern_is_case_sensitive_only_with_uppercase() {
+        let process = process();
+        assert!(filter("train").matches(0, &process));
+        assert!(!filter("ALICE").matches(0, &process));
+        assert!(filter("Train").matches(0, &process));
+        assert!(!filter("TRAIN").matches(0, &process));
+    }
+
+    #[test]
+    fn invalid_pattern_keeps_the_last_valid_one() {
+        let mut filter = filter("alice");
+        filter.set_pattern("alice(".to_string());
+        assert!(filter.error.is_some());
+        assert_eq!(filter.pattern, "alice(");
+        assert!(filter.matches(0, &process()));
+        assert!(!filter.matches(
+            0,
+            &GpuProcessInfo {
+                username: "bob".to_string(),
+                ..process()
+            }
+        ));
+
+        filter.set_pattern("alice".to_string());
+        assert!(filter.error.is_none());
+    }
+
+    #[test]
+    fn only_mine_and_only_compute_narrow_the_pattern() {
+        let mut filter = filter("python");
+        filter.only_user = Some("bob".to_string());
+        assert!(filter.is_active());
+        assert!(!filter.matches(0, &process()));
+
+        filter.only_user = Some("alice".to_string());
+        assert!(filter.matches(0, &process()));
+
+        filter.only_compute = true;
+        let graphics = GpuProcessInfo {
+            process_type: Some(ProcessType::Graphics),
+            ..process()
+        };
+        let both = GpuProcessInfo {
+            process_type: Some(ProcessType::ComputeGraphics),
+            ..process()
+        };
+        assert!(filter.matches(0, &process()));
+        assert!(filter.matches(0, &both));
+        assert!(!filter.matches(0, &graphics));
+    }
+}
@@ -0,0 +1,501 @@
+use crate::app_state::ProcessKey;
+use crate::gpu::info::GpuInfo;
+use crate::gpu::process::GpuProcessInfo;
+use crate::ui::columns::{container_name, job_id, pod_name, MemoryMetric, ProcessColumn};
+use crate::utils::formatting::{format_memory_size, format_or_na};
+
+/// How the process table groups processes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Grouping {
+    None,
+    // Distributed job ranks under the launcher that started them
+    Launcher,
+    // Everything a user runs, to see who holds how much
+    User,
+    // The processes on each GPU, to see who is using it
+    Gpu,
+    // Processes of a container, or of all containers of a Kubernetes pod
+    Container,
+    // Processes of a Slurm job, across its steps
+    Job,
+}
+
+impl Grouping {
+    pub fn next(self) -> Grouping {
+        match self {
+            Grouping::None => Grouping::Launcher,
+            Grouping::Launcher => Grouping::User,
+            Grouping::User => Grouping::Gpu,
+            Grouping::Gpu => Grouping::Container,
+            Grouping::Container => Grouping::Job,
+            Grouping::Job => Grouping::None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Grouping::None => "none",
+            Grouping::Launcher => "launcher",
+            Grouping::User => "user",
+            Grouping::Gpu => "gpu",
+            Grouping::Container => "container",
+            Grouping::Job => "job",
+        }
+    }
+
+    /// The group a process belongs to, or `None` if it stands on its own.
+    fn group_of(
+        self,
+        gpu_index: usize,
+        process: &GpuProcessInfo,
+        gpu_infos: &[GpuInfo],
+    ) -> Option<GroupInfo> {
+        match self {
+            Grouping::None => None,
+            Grouping::Launcher => process.launcher.as_ref().map(|launcher| GroupInfo {
+                key: launcher.pid.to_string(),
+                pid: Some(launcher.pid),
+                label: launcher.command.clone(),
+            }),
+            Grouping::User => Some(GroupInfo {
+                key: process.username.clone(),
+                pid: None,
+                label: process.username.clone(),
+            }),
+            Grouping::Gpu => {
+                let gpu = gpu_infos.iter().find(|gpu| gpu.index == gpu_index);
+                let free =
+                    gpu.and_then(|gpu| Some(gpu.memory_total?.saturating_sub(gpu.memory_used?)));
+                Some(GroupInfo {
+                    key: gpu_index.to_string(),
+                    pid: None,
+                    label: format!(
+                        "GPU {} {}: {} free",
+                        gpu_index,
+                        gpu.map_or("", |gpu| gpu.name.as_str()),
+                        format_or_na(free, format_memory_size)
+                    ),
+                })
+            }
+            Grouping::Container => {
+                let container = process.container.as_ref()?;
+                Some(match &container.pod {
+                    Some(pod) => GroupInfo {
+                        key: pod.uid.clone(),
+                        pid: None,
+                        label: format!("pod {}", pod.display_name()),
+                    },
+                    None => GroupInfo {
+                        key: container.id.clone(),
+                        pid: None,
+                        label: match container.runtime {
+                            Some(runtime) => format!(
+                                "{} container {}",
+                                runtime.label(),
+                                container.display_name()
+                            ),
+                            None => format!("container {}", container.display_name()),
+                        },
+                    },
+                })
+            }
+            Grouping::Job => {
+                let job = process.slurm_job.as_ref()?;
+                let id = job.display_id();
+                Some(GroupInfo {
+                    key: id.clone(),
+                    pid: None,
+                    label: match &job.name {
+                        Some(name) => format!("job {} {}", id, name),
+                        None => format!("job {}", id),
+                    },
+                })
+            }
+        }
+    }
+}
+
+struct GroupInfo {
+    key: String,
+    pid: Option<u32>,
+    label: String,
+}
+
+/// Identifies a row of the process table, which is either a process or the
+/// header of a group.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RowKey {
+    Process(ProcessKey),
+    Group(String),
+}
+
+pub struct ProcessGroup<'a> {
+    pub key: String,
+    pub grouping: Grouping,
+    // Process the group is named after, like the launcher
+    pub pid: Option<u32>,
+    pub label: String,
+    pub members: Vec<(usize, &'a GpuProcessInfo)>,
+    pub collapsed: bool,
+}
+
+impl ProcessGroup<'_> {
+    /// Text of the column in the group's header row. GPU memory is summed over
+    /// the rows, CPU and host memory over the processes, and GPU engine
+    /// utilization over the rows on the busiest GPU; columns that don't add up
+    /// are shown only when all members agree on them.
+    pub fn value(&self, column: ProcessColumn, memory_metric: MemoryMetric) -> String {
+        let memory = |value: fn(&GpuProcessInfo) -> Option<u64>| {
+            format_or_na(self.sum(value), format_memory_size)
+        };
+        match column {
+            ProcessColumn::Gpu => {
+                let mut gpus: Vec<usize> = self.members.iter().map(|&(gpu, _)| gpu).collect();
+                gpus.sort_unstable();
+                gpus.dedup();
+                if gpus.len() == 1 {
+                    gpus[0].to_string()
+                } else {
+                    "*".to_string()
+                }
+            }
+            ProcessColumn::Pid => format_or_na(self.pid, |p| p.to_string()),
+            ProcessColumn::User => self
+                .common(|p| p.username.clone())
+                .unwrap_or_else(|| "*".to_string()),
+            ProcessColumn::GpuMemory => {
+                format_or_na(self.sum_rows(|p| p.used_gpu_memory), format_memory_size)
+            }
+            ProcessColumn::Sm
+            | ProcessColumn::MemoryUtilization
+            | ProcessColumn::Encoder
+            | ProcessColumn::Decoder => {
+                format_or_na(self.busiest_gpu(|p| column.utilization(p)), |u| {
+                    format!("{}%", u)
+                })
+            }
+            ProcessColumn::Cpu => format_or_na(self.sum(|p| p.cpu_usage), |u| format!("{:.1}%", u)),
+            ProcessColumn::Memory => {
+                format_or_na(self.sum(|p| memory_metric.value(p)), format_memory_size)
+            }
+            ProcessColumn::Rss => memory(|p| Some(p.memory_usage)),
+            ProcessColumn::Pss => memory(|p| p.memory_pss),
+            ProcessColumn::Uss => memory(|p| p.memory_uss),
+            ProcessColumn::Swap => memory(|p| p.memory_swap),
+            ProcessColumn::Threads => format_or_na(self.sum(|p| p.threads), |t| t.to_string()),
+            ProcessColumn::Container => self.common(container_name).flatten().unwrap_or_default(),
+            ProcessColumn::Pod => self.common(pod_name).flatten().unwrap_or_default(),
+            ProcessColumn::Job => self.common(job_id).flatten().unwrap_or_default(),
+            ProcessColumn::Cgroup => self
+                .common(|p| p.cgroup.clone())
+                .flatten()
+                .unwrap_or_default(),
+            ProcessColumn::Command => format!(
+                "{} {} ({})",
+                if self.collapsed { '▶' } else { '▼' },
+                self.label,
+                self.summary()
+            ),
+            _ => String::new(),
+        }
+    }
+
+    /// Process count, plus the GPUs a user's processes are spread over or the
+    /// users sharing a GPU.
+    fn summary(&self) -> String {
+        let count = match self.processes().len() {
+            1 => "1 process".to_string(),
+            count => format!("{} processes", count),
+        };
+        match self.grouping {
+            Grouping::User => {
+                let mut gpus: Vec<usize> = self.members.iter().map(|&(gpu, _)| gpu).collect();
+                gpus.sort_unstable();
+                gpus.dedup();
+                match gpus.len() {
+                    1 => format!("{} on 1 GPU", count),
+                    gpus => format!("{} on {} GPUs", count, gpus),
+                }
+            }
+            Grouping::Gpu => {
+                let mut users: Vec<&str> = self
+                    .members
+                    .iter()
+                    .map(|(_, p)| p.username.as_str())
+                    .collect();
+                users.sort_unstable();
+                users.dedup();
+                format!("{} of {}", count, users.join(", "))
+            }
+            Grouping::None | Grouping::Launcher | Grouping::Container | Grouping::Job => count,
+        }
+    }
+
+    /// The member processes, each listed once. A process using several GPUs
+    /// has a row for each of them.
+    fn processes(&self) -> Vec<&GpuProcessInfo> {
+        let mut processes: Vec<&GpuProcessInfo> = Vec::new();
+        for &(_, process) in &self.members {
+            let same = |p: &&GpuProcessInfo| {
+                (p.pid, p.proc_pid, p.start_time_ms)
+                    == (process.pid, process.proc_pid, process.start_time_ms)
+            };
+            if !processes.iter().any(same) {
+                processes.push(process);
+            }
+        }
+        processes
+    }
+
+    /// Sum of a per-process figure, or `None` if no member has it.
+    fn sum<T: std::iter::Sum<T>>(&self, value: impl Fn(&GpuProcessInfo) -> Option<T>) -> Option<T> {
+        let values: Vec<T> = self.processes().into_iter().filter_map(value).collect();
+        (!values.is_empty()).then(|| values.into_iter().sum())
+    }
+
+    /// Sum of a per-GPU figure over the rows, or `None` if no row has it.
+    fn sum_rows<T: std::iter::Sum<T>>(
+        &self,
+        value: impl Fn(&GpuProcessInfo) -> Option<T>,
+    ) -> Option<T> {
+        let values: Vec<T> = self.members.iter().filter_map(|(_, p)| value(p)).collect();
+        (!values.is_empty()).then(|| values.into_iter().sum())
+    }
+
+    /// Sum of a percentage of one GPU over the members on the same GPU, for
+    /// the GPU where it is highest. Adding shares of different GPUs would go
+    /// past 100%.
+    fn busiest_gpu(&self, value: impl Fn(&GpuProcessInfo) -> Option<u32>) -> Option<u32> {
+        let mut totals: Vec<(usize, u32)> = Vec::new();
+        for &(gpu, process) in &self.members {
+            let Some(value) = value(process) else {
+                continue;
+            };
+            match totals.iter_mut().find(|(index, _)| *index == gpu) {
+                Some((_, total)) => *total += value,
+                None => totals.push((gpu, value)),
+            }
+        }
+        totals.into_iter().map(|(_, total)| total).max()
+    }
+
+    /// The value all members share, or `None` if they differ.
+    fn common<T: PartialEq>(&self, value: impl Fn(&GpuProcessInfo) -> T) -> Option<T> {
+        let mut values = self.members.iter().map(|(_, p)| value(p));
+        let first = values.next()?;
+        values.all(|v| v == first).then_some(first)
+    }
+}
+
+pub enum TableRow<'a> {
+    Group(ProcessGroup<'a>),
+    Process {
+        gpu_index: usize,
+        process: &'a GpuProcessInfo,
+        // Position under a group header, if the process is in a group
+        branch: Option<Branch>,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Branch {
+    Middle,
+    Last,
+}
+
+impl Branch {
+    /// Tree line drawn before the command of a group member.
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Branch::Middle => "├─ ",
+            Branch::Last => "└─ ",
+        }
+    }
+}
+
+impl<'a> TableRow<'a> {
+    pub fn key(&self) -> RowKey {
+        match self {
+            TableRow::Group(group) => RowKey::Group(group.key.clone()),
+            TableRow::Process {
+                gpu_index, process, ..
+            } => RowKey::Process((*gpu_index, process.pid)),
+        }
+    }
+
+    /// The processes an action on this row applies to.
+    pub fn processes(&self) -> Vec<(usize, &'a GpuProcessInfo)> {
+        match self {
+            TableRow::Group(group) => group.members.clone(),
+            TableRow::Process {
+                gpu_index, process, ..
+            } => vec![(*gpu_index, *process)],
+        }
+    }
+}
+
+/// Builds the rows of the process table from the sorted processes. A group
+/// takes the place of its first member, so groups are ordered by their top
+/// process under the current sort.
+pub fn build_rows<'a>(
+    processes: Vec<(usize, &'a GpuProcessInfo)>,
+    gpu_infos: &[GpuInfo],
+    grouping: Grouping,
+    is_collapsed: impl Fn(&str) -> bool,
+) -> Vec<TableRow<'a>> {
+    enum Entry<'a> {
+        Group(usize),
+        Process(usize, &'a GpuProcessInfo),
+    }
+
+    let mut groups: Vec<ProcessGroup<'a>> = Vec::new();
+    let mut entries = Vec::new();
+    for (gpu_index, process) in processes {
+        let Some(info) = grouping.group_of(gpu_index, process, gpu_infos) else {
+            entries.push(Entry::Process(gpu_index, process));
+            continue;
+        };
+        match groups.iter().position(|group| group.key == info.key) {
+            Some(index) => groups[index].members.push((gpu_index, process)),
+            None => {
+                entries.push(Entry::Group(groups.len()));
+                groups.push(ProcessGroup {
+                    collapsed: is_collapsed(&info.key),
+                    key: info.key,
+                    grouping,
+                    pid: info.pid,
+                    label: info.label,
+                    members: vec![(gpu_index, process)],
+                });
+            }
+        }
+    }
+
+    let mut groups: Vec<Option<ProcessGroup<'a>>> = groups.into_iter().map(Some).collect();
+    let mut rows = Vec::new();
+    for entry in entries {
+        match entry {
+            Entry::Process(gpu_index, process) => rows.push(TableRow::Process {
+                gpu_index,
+                process,
+                branch: None,
+            }),
+            Entry::Group(index) => {
+                let group = groups[index].take().expect("each group is listed once");
+                let members = if group.collapsed {
+                    Vec::new()
+                } else {
+                    group.members.clone()
+                };
+                rows.push(TableRow::Group(group));
+                let last = members.len().saturating_sub(1);
+                rows.extend(
+                    members
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, (gpu_index, process))| TableRow::Process {
+                            gpu_index,
+                            process,
+                            branch: Some(if i == last {
+                                Branch::Last
+                            } else {
+                                Branch::Middle
+                            }),
+                        }),
+                );
+            }
+        }
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gpu::process::{Launcher, ProcessType};
+
+    fn process(pid: u32, username: &str) -> GpuProcessInfo {
+        GpuProcessInfo {
+            proc_pid: Some(pid),
+            username: username.to_string(),
+            command: "python train.py".to_string(),
+            cpu_usage: Some(100.0),
+            memory_usage: 1024,
+            threads: Some(8),
+            start_time_ms: Some(1_000),
+            launcher: Some(Launcher {
+                pid: 10,
+                command: "torchrun train.py".to_string(),
+            }),
+            foreign: false,
+            ..GpuProcessInfo::foreign(pid, Some(4096), ProcessType::Compute)
+        }
+    }
+
+    fn group<'a>(rows: &'a [TableRow<'a>]) -> &'a ProcessGroup<'a> {
+        match &rows[0] {
+            TableRow::Group(group) => group,
+            TableRow::Process { .. } => panic!("expected a group header"),
+        }
+    }
+
+    #[test]
+    fn process_on_two_gpus_is_counted_once_per_launcher() {
+        let (a, b) = (process(100, "alice"), process(101, "alice"));
+        let rows = build_rows(
+            vec![(0, &a), (1, &a), (0, &b)],
+            &[],
+            Grouping::Launcher,
+            |_| false,
+        );
+        let group = group(&rows);
+        let value = |column| group.value(column, MemoryMetric::Rss);
+
+        // GPU memory is held on each GPU separately
+        assert_eq!(
+            value(ProcessColumn::GpuMemory),
+            format_memory_size(3 * 4096)
+        );
+        assert_eq!(value(ProcessColumn::Cpu), "200.0%");
+        assert_eq!(value(ProcessColumn::Memory), format_memory_size(2 * 1024));
+        assert_eq!(value(ProcessColumn::Threads), "16");
+        assert!(value(ProcessColumn::Command).ends_with("(2 processes)"));
+    }
+
+    #[test]
+    fn user_totals_count_each_process_once() {
+        let (a, b, c) = (
+            process(100, "alice"),
+            process(101, "alice"),
+            process(200, "bob"),
+        );
+        let rows = build_rows(
+            vec![(0, &a), (1, &a), (2, &a), (1, &b), (3, &c)],
+            &[],
+            Grouping::User,
+            |_| false,
+        );
+        let alice = group(&rows);
+        let value = |column| alice.value(column, MemoryMetric::Rss);
+        assert_eq!(alice.label, "alice");
+        assert_eq!(value(ProcessColumn::Cpu), "200.0%");
+        assert_eq!(value(ProcessColumn::Rss), format_memory_size(2 * 1024));
+        assert_eq!(
+            value(ProcessColumn::GpuMemory),
+            format_memory_size(4 * 4096)
+        );
+        assert!(value(ProcessColumn::Command).ends_with("(2 processes on 3 GPUs)"));
+    }
+
+    #[test]
+    fn reused_pid_is_another_process() {
+        let a = process(100, "alice");
+        let b = GpuProcessInfo {
+            start_time_ms: Some(2_000),
+            ..process(100, "alice")
+        };
+        let rows = build_rows(vec![(0, &a), (1, &b)], &[], Grouping::Launcher, |_| false);
+        let value = group(&rows).value(ProcessColumn::Cpu, MemoryMetric::Rss);
+        assert_eq!(value, "200.0%");
+    }
+}
@@ -0,0 +1,104 @@
+use crate::app_state::AppState;
+use crate::ui::columns::GpuColumn;
+use crossterm::event::KeyCode;
+
+/// Handles the keys shared by the live view and replay: process navigation,
+/// GPU tab switching and view mode selection. Returns `true` if the key was
+/// consumed.
+pub fn handle_navigation_key(app_state: &mut AppState, code: KeyCode) -> bool {
+    match code {
+        KeyCode::Up | KeyCode::Char('k') => app_state.move_selection(-1),
+        KeyCode::Down | KeyCode::Char('j') => app_state.move_selection(1),
+        KeyCode::PageUp => app_state.move_selection_by_pages(-1),
+        KeyCode::PageDown => app_state.move_selection_by_pages(1),
+        KeyCode::Home | KeyCode::Char('g') => app_state.select_row(0),
+        KeyCode::End | KeyCode::Char('G') => app_state.select_row(usize::MAX),
+        KeyCode::Left if app_state.use_tabbed_graphs && app_state.selected_gpu_tab > 0 => {
+            app_state.selected_gpu_tab -= 1;
+        }
+        KeyCode::Right
+            if app_state.use_tabbed_graphs
+                && app_state.selected_gpu_tab + 1 < app_state.gpu_infos.len() =>
+        {
+            app_state.selected_gpu_tab += 1;
+        }
+        KeyCode::Char('d') => {
+            app_state.use_tabbed_graphs = false;
+            app_state.use_bar_charts = false;
+        }
+        KeyCode::Char('t') => {
+            app_state.use_tabbed_graphs = true;
+            app_state.use_bar_charts = false;
+        }
+        KeyCode::Char('b') => {
+            app_state.use_tabbed_graphs = false;
+            app_state.use_bar_charts = true;
+        }
+        KeyCode::Char('v') => {
+            app_state.graph_metrics[0] = app_state.graph_metrics[0].next();
+        }
+        KeyCode::Char('V') => {
+            app_state.graph_metrics[1] = app_state.graph_metrics[1].next();
+        }
+        KeyCode::Char('o') => app_state.column_picker = Some(0),
+        KeyCode::Char('m') => app_state.memory_metric = app_state.memory_metric.next(),
+        KeyCode::Char(' ') => app_state.toggle_mark(),
+        KeyCode::Char('A') => app_state.mark_where(|_, _| true),
+        KeyCode::Char('U') => {
+            if let Some(&(_, selected)) = app_state.selected_processes().first() {
+                let username = selected.username.clone();
+                app_state.mark_where(|_, p| p.username == username);
+            }
+        }
+        KeyCode::Char('I') => {
+            if let Some(&(gpu_index, _)) = app_state.selected_processes().first() {
+                app_state.mark_where(|gpu, _| gpu == gpu_index);
+            }
+        }
+        KeyCode::Esc => app_state.marked_processes.clear(),
+        KeyCode::Char('/') => app_state.filter.editing = true,
+        KeyCode::Char('u') => app_state.update_filter(|filter| filter.toggle_only_mine()),
+        KeyCode::Char('c') => {
+            app_state.update_filter(|filter| filter.only_compute = !filter.only_compute)
+        }
+        KeyCode::Char('T') => app_state.cycle_grouping(),
+        KeyCode::Char('z') => app_state.toggle_collapse(),
+        KeyCode::Char('s') => app_state.cycle_sort_column(),
+        KeyCode::Char('S') => app_state.sort_descending = !app_state.sort_descending,
+        _ => return false,
+    }
+    true
+}
+
+/// Handles keys while the GPU column picker is open. Every key is consumed so
+/// nothing else reacts until the picker is closed.
+pub fn handle_column_picker_key(app_state: &mut AppState, code: KeyCode) {
+    let Some(cursor) = app_state.column_picker else {
+        return;
+    };
+    match code {
+        KeyCode::Up | KeyCode::Char('k') if cursor > 0 => {
+            app_state.column_picker = Some(cursor - 1);
+        }
+        KeyCode::Down | KeyCode::Char('j') if cursor + 1 < GpuColumn::ALL.len() => {
+            app_state.column_picker = Some(cursor + 1);
+        }
+        KeyCode::Char(' ') | KeyCode::Enter => {
+            let column = GpuColumn::ALL[cursor];
+            let visible = !app_state.gpu_columns.contains(&column);
+            // Rebuild from the full list to keep the table order stable
+            app_state.gpu_columns = GpuColumn::ALL
+                .into_iter()
+                .filter(|&c| {
+                    if c == column {
+                        visible
+                    } else {
+                        app_state.gpu_columns.contains(&c)
+                    }
+                })
+                .collect();
+        }
+        KeyCode::Esc | KeyCode::Char('o') => app_state.column_picker = None,
+        _ => {}
+    }
+}
@@ -0,0 +1,275 @@
+use crate::app_state::{AppState, ProcessKey};
+use crate::gpu::process::GpuProcessInfo;
+use crate::utils::system::{read_start_time_ms, send_signal};
+use crossterm::event::KeyCode;
+use nix::sys::signal::Signal;
+use std::error::Error;
+use std::io::{Error as IoError, ErrorKind};
+use std::time::Instant;
+
+/// Signals offered by the kill dialog, with a short description.
+pub const SIGNALS: [(Signal, &str); 5] = [
+    (Signal::SIGTERM, "Terminate gracefully"),
+    (Signal::SIGKILL, "Kill immediately"),
+    (Signal::SIGINT, "Interrupt, like Ctrl-C"),
+    (Signal::SIGSTOP, "Pause"),
+    (Signal::SIGCONT, "Resume a paused process"),
+];
+
+/// The process a signal is sent to, as it was when the dialog opened.
+pub struct KillTarget {
+    pub key: ProcessKey,
+    pub username: String,
+    pub command: String,
+    pub used_gpu_memory: Option<u64>,
+    // Tells the process apart from a later one that reuses its PID
+    pub start_time_ms: Option<u64>,
+}
+
+pub struct KillDialog {
+    // The marked processes, or the selected one if none are marked
+    pub targets: Vec<KillTarget>,
+    // Index into `SIGNALS`
+    pub signal: usize,
+    // Send SIGKILL if the process still holds GPU memory after the timeout
+    pub escalate: bool,
+}
+
+/// A process that gets SIGKILL at `deadline` unless it released its GPU
+/// memory by then.
+pub struct Escalation {
+    pub key: ProcessKey,
+    pub start_time_ms: Option<u64>,
+    pub command: String,
+    pub deadline: Instant,
+}
+
+/// Opens the kill dialog for the marked processes, or the selected process
+/// or group when nothing is marked.
+pub fn open_kill_dialog(app_state: &mut AppState) {
+    let processes = if app_state.marked_processes.is_empty() {
+        app_state.selected_processes()
+    } else {
+        app_state
+            .visible_processes()
+            .into_iter()
+            .filter(|&(gpu, p)| app_state.marked_processes.contains(&(gpu, p.pid)))
+            .collect()
+    };
+    // Their PIDs would name some other process here, or none at all
+    let (foreign, processes): (Vec<_>, Vec<_>) =
+        processes.into_iter().partition(|(_, p)| p.foreign);
+    let foreign_pids: Vec<u32> = foreign.iter().map(|(_, p)| p.pid).collect();
+    let targets: Vec<KillTarget> = processes
+        .into_iter()
+        .map(|(gpu, p)| KillTarget {
+            key: (gpu, p.pid),
+            username: p.username.clone(),
+            command: p.command.clone(),
+            used_gpu_memory: p.used_gpu_memory,
+            start_time_ms: p.start_time_ms,
+        })
+        .collect();
+    if !foreign_pids.is_empty() {
+        app_state.error_message = Some(format!(
+            "Can't signal {}: outside nviwatch's PID namespace, as when it runs in a container without --pid=host",
+            format_pids(&foreign_pids)
+        ));
+    }
+    if targets.is_empty() {
+        if foreign_pids.is_empty() {
+            app_state.error_message = Some("No process selected".to_string());
+        }
+        return;
+    }
+    app_state.kill_dialog = Some(KillDialog {
+        targets,
+        signal: 0,
+        escalate: false,
+    });
+}
+
+/// Handles keys while the kill dialog is open. Returns `true` if a signal was
+/// sent, so the caller can refresh the process list right away.
+pub fn handle_kill_dialog_key(app_state: &mut AppState, code: KeyCode) -> bool {
+    let Some(dialog) = app_state.kill_dialog.as_mut() else {
+        return false;
+    };
+    match code {
+        KeyCode::Up | KeyCode::Char('k') if dialog.signal > 0 => dialog.signal -= 1,
+        KeyCode::Down | KeyCode::Char('j') if dialog.signal + 1 < SIGNALS.len() => {
+            dialog.signal += 1
+        }
+        KeyCode::Char('e') => dialog.escalate = !dialog.escalate,
+        KeyCode::Enter | KeyCode::Char('y') => {
+            let dialog = app_state.kill_dialog.take().expect("dialog is open");
+            return confirm(app_state, dialog);
+        }
+        KeyCode::Esc | KeyCode::Char('n') | KeyCode::Char('q') => app_state.kill_dialog = None,
+        _ => {}
+    }
+    false
+}
+
+fn confirm(app_state: &mut AppState, dialog: KillDialog) -> bool {
+    let (signal, _) = SIGNALS[dialog.signal];
+    let escalate = dialog.escalate && signal != Signal::SIGKILL;
+    let mut sent = Vec::new();
+    let mut denied = Vec::new();
+    let mut failed = Vec::new();
+    let mut gone = Vec::new();
+
+    // A process on several GPUs only gets the signal once
+    let mut signalled_pids = Vec::new();
+    for target in dialog.targets {
+        let (_, pid) = target.key;
+        if signalled_pids.contains(&pid) || gone.contains(&pid) {
+            continue;
+        }
+        // The process may have exited while the dialog was open, and its PID
+        // been taken by an unrelated one
+        if !current_process(app_state, target.key, target.start_time_ms).is_some_and(is_running) {
+            gone.push(pid);
+            continue;
+        }
+        signalled_pids.push(pid);
+        match send_signal(pid, &target.command, signal) {
+            Ok(_) => {
+                sent.push(pid);
+                if escalate {
+                    app_state.escalations.push(Escalation {
+                        key: target.key,
+                        start_time_ms: target.start_time_ms,
+                        command: target.command,
+                        deadline: Instant::now() + app_state.escalation_timeout,
+                    });
+                }
+            }
+            Err(e) if is_permission_denied(e.as_ref()) => denied.push(pid),
+            Err(e) => failed.push(e.to_string()),
+        }
+    }
+
+    let mut summary = Vec::new();
+    if !sent.is_empty() {
+        summary.push(format!(
+            "Sent {} to {}",
+            signal.as_str(),
+            format_pids(&sent)
+        ));
+        if escalate {
+            summary.push(format!(
+                "SIGKILL follows in {}s for any still holding GPU memory",
+                app_state.escalation_timeout.as_secs()
+            ));
+        }
+    }
+    if !denied.is_empty() {
+        summary.push(format!("Permission denied for {}", format_pids(&denied)));
+    }
+    if !gone.is_empty() {
+        summary.push(format!(
+            "Skipped {}, no longer running on the GPU",
+            format_pids(&gone)
+        ));
+    }
+    summary.extend(failed);
+    let summary = summary.join("; ");
+
+    if denied.is_empty() && gone.is_empty() && signalled_pids.len() == sent.len() {
+        app_state.error_message = None;
+        app_state.status_message = Some(summary);
+    } else {
+        app_state.error_message = Some(summary);
+    }
+    !sent.is_empty()
+}
+
+/// The process with `key` in the latest sample, if it is still the one that
+/// started at `start_time_ms`.
+fn current_process(
+    app_state: &AppState,
+    key: ProcessKey,
+    start_time_ms: Option<u64>,
+) -> Option<&GpuProcessInfo> {
+    let (gpu_index, pid) = key;
+    app_state
+        .gpu_infos
+        .iter()
+        .filter(|gpu| gpu.index == gpu_index)
+        .flat_map(|gpu| &gpu.processes)
+        .find(|process| process.pid == pid && process.start_time_ms == start_time_ms)
+}
+
+/// Whether the process is still running, checked against `/proc` right
+/// before signalling since the last sample can be a refresh interval old.
+/// Processes whose start time can't be compared are not signalled.
+fn is_running(process: &GpuProcessInfo) -> bool {
+    match (process.proc_pid, process.start_time_ms) {
+        (Some(proc_pid), Some(start_time_ms)) => {
+            read_start_time_ms(proc_pid) == Some(start_time_ms)
+        }
+        _ => false,
+    }
+}
+
+fn is_permission_denied(error: &(dyn Error + 'static)) -> bool {
+    error
+        .downcast_ref::<IoError>()
+        .is_some_and(|e| e.kind() == ErrorKind::PermissionDenied)
+}
+
+fn format_pids(pids: &[u32]) -> String {
+    let pids: Vec<String> = pids.iter().map(|pid| pid.to_string()).collect();
+    match pids.len() {
+        1 => format!("process {}", pids[0]),
+        count => format!("{} processes ({})", count, pids.join(", ")),
+    }
+}
+
+/// Sends SIGKILL to processes whose escalation deadline passed while they
+/// still hold GPU memory, and reports processes that exited in time. Called
+/// after every sample. Returns `true` if a signal was sent.
+pub fn check_escalations(app_state: &mut AppState) -> bool {
+    let now = Instant::now();
+    let mut released = Vec::new();
+    let mut killed = Vec::new();
+    let escalations = std::mem::take(&mut app_state.escalations);
+    for escalation in escalations {
+        let (_, pid) = escalation.key;
+        // A process whose memory is unknown holds the GPU until it exits
+        let holding = current_process(app_state, escalation.key, escalation.start_time_ms)
+            .filter(|process| process.used_gpu_memory != Some(0));
+
+        if holding.is_none() {
+            released.push(pid);
+        } else if now < escalation.deadline {
+            app_state.escalations.push(escalation);
+        } else if killed.contains(&pid) {
+            // Already killed through its entry for another GPU
+        } else if !holding.is_some_and(is_running) {
+            // Exited since the last sample
+            released.push(pid);
+        } else {
+            match send_signal(pid, &escalation.command, Signal::SIGKILL) {
+                Ok(_) => killed.push(pid),
+                Err(e) => app_state.error_message = Some(e.to_string()),
+            }
+        }
+    }
+
+    let mut summary = Vec::new();
+    if !released.is_empty() {
+        summary.push(format!("Released GPU memory: {}", format_pids(&released)));
+    }
+    if !killed.is_empty() {
+        summary.push(format!(
+            "Still holding GPU memory, sent SIGKILL to {}",
+            format_pids(&killed)
+        ));
+    }
+    if !summary.is_empty() {
+        app_state.status_message = Some(summary.join("; "));
+    }
+    !killed.is_empty()
+}
@@ -1,2 +1,10 @@
+pub mod columns;
+pub mod details;
+pub mod events;
+pub mod filter;
+pub mod grouping;
+pub mod input;
+pub mod kill;
 pub mod render;
+pub mod terminal;
 pub mod widgets;
@@ -1,14 +1,19 @@
 use crate::app_state::AppState;
-use crate::gpu::info::GpuInfo;
-use crate::ui::widgets::{render_footer, render_gpu_graphs};
-use crate::utils::formatting::format_memory_size;
+use crate::session::unix_time_ms;
+use crate::ui::columns::ProcessColumn;
+use crate::ui::grouping::{Grouping, TableRow};
+use crate::ui::widgets::{
+    render_column_picker, render_detail_pane, render_footer, render_gpu_graphs, render_kill_dialog,
+};
 use ratatui::layout::Rect;
 use ratatui::layout::{Constraint, Direction, Layout};
 use ratatui::style::{Color, Modifier, Style};
-use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table};
+use ratatui::widgets::{
+    Block, Borders, Cell, Paragraph, Row, Scrollbar, ScrollbarOrientation, ScrollbarState, Table,
+};
 use ratatui::Frame;
 
-pub fn ui(f: &mut Frame, app_state: &AppState) {
+pub fn ui(f: &mut Frame, app_state: &mut AppState) {
     let num_gpus = app_state.gpu_infos.len();
     let gpu_info_percentage = {
         let base_percentage = num_gpus as u16 * 5;
@@ -27,160 +32,130 @@ pub fn ui(f: &mut Frame, app_state: &AppState) {
         )
         .split(f.area());
 
-    render_gpu_info(f, chunks[0], &app_state.gpu_infos);
+    render_gpu_info(f, chunks[0], app_state);
     render_gpu_graphs(f, chunks[1], app_state);
     render_process_list(f, chunks[2], app_state);
+
+    if let Some(cursor) = app_state.column_picker {
+        render_column_picker(f, app_state, cursor);
+    }
+    if let Some(pane) = &app_state.detail_pane {
+        render_detail_pane(f, app_state, pane);
+    }
+    if let Some(dialog) = &app_state.kill_dialog {
+        render_kill_dialog(f, app_state, dialog);
+    }
 }
 
-pub fn render_gpu_info(f: &mut Frame, area: Rect, gpu_infos: &[GpuInfo]) {
+pub fn render_gpu_info(f: &mut Frame, area: Rect, app_state: &AppState) {
     let block = Block::default().borders(Borders::ALL).title("GPU Info");
     f.render_widget(block.clone(), area);
     let gpu_area = block.inner(area);
+    let gpu_infos = &app_state.gpu_infos;
 
-    // Calculate maximum widths for each column
-    let max_index_width = gpu_infos
-        .iter()
-        .map(|info| info.index.to_string().len())
-        .max()
-        .unwrap_or(0)
-        .max(3);
-    let max_name_width = gpu_infos
-        .iter()
-        .map(|info| info.name.len())
-        .max()
-        .unwrap_or(0)
-        .max(4);
-    let max_temp_width = gpu_infos
-        .iter()
-        .map(|info| format!("{}°C", info.temperature).len())
-        .max()
-        .unwrap_or(0)
-        .max(4);
-    let max_util_width = gpu_infos
-        .iter()
-        .map(|info| format!("{}%", info.utilization).len())
-        .max()
-        .unwrap_or(0)
-        .max(4);
-    let max_memory_width = gpu_infos
+    let mut headers = vec!["GPU", "Name"];
+    let mut colors = vec![Color::Cyan, Color::Green];
+    for column in &app_state.gpu_columns {
+        headers.push(column.header());
+        colors.push(column.color());
+    }
+
+    let cells: Vec<Vec<String>> = gpu_infos
         .iter()
         .map(|info| {
-            format!(
-                "{}/{}MB",
-                info.memory_used / 1_048_576,
-                info.memory_total / 1_048_576
-            )
-            .len()
+            let mut row = vec![info.index.to_string(), info.name.clone()];
+            row.extend(
+                app_state
+                    .gpu_columns
+                    .iter()
+                    .map(|column| column.value(info)),
+            );
+            row
         })
-        .max()
-        .unwrap_or(0)
-        .max(6);
-    let max_power_width = gpu_infos
-        .iter()
-        .map(|info| format!("{}/{}W", info.power_usage, info.power_limit).len())
-        .max()
-        .unwrap_or(0)
-        .max(5);
-    let max_clock_width = gpu_infos
+        .collect();
+
+    // Size each column to its widest value plus some padding
+    let mut widths: Vec<Constraint> = headers
         .iter()
-        .map(|info| format!("{}MHz", info.clock_freq).len())
-        .max()
-        .unwrap_or(0)
-        .max(5);
+        .enumerate()
+        .map(|(column, header)| {
+            let max_width = cells
+                .iter()
+                .map(|row| row[column].chars().count())
+                .max()
+                .unwrap_or(0)
+                .max(header.len());
+            Constraint::Length(max_width as u16 + 2)
+        })
+        .collect();
+    // The status column takes whatever space is left
+    widths.push(Constraint::Min(6));
 
-    // Add some padding to each width
-    let index_width = max_index_width + 2;
-    let name_width = max_name_width + 2;
-    let temp_width = max_temp_width + 2;
-    let util_width = max_util_width + 2;
-    let memory_width = max_memory_width + 2;
-    let power_width = max_power_width + 2;
-    let clock_width = max_clock_width + 2;
+    let rows: Vec<Row> = cells
+        .into_iter()
+        .zip(gpu_infos)
+        .map(|(row, info)| {
+            let mut cells: Vec<Cell> = row
+                .into_iter()
+                .zip(colors.iter().copied())
+                .map(|(text, color)| Cell::from(text).style(Style::default().fg(color)))
+                .collect();
+            cells.push(match &info.error {
+                Some(error) => {
+                    Cell::from(format!("⚠ {}", error)).style(Style::default().fg(Color::Red))
+                }
+                None => Cell::from("OK").style(Style::default().fg(Color::Green)),
+            });
+            Row::new(cells)
+        })
+        .collect();
 
-    let rows: Vec<Row> = gpu_infos
+    let mut header_cells: Vec<Cell> = headers
         .iter()
-        .map(|info| {
-            let cells = vec![
-                Cell::from(info.index.to_string()).style(Style::default().fg(Color::Cyan)),
-                Cell::from(info.name.as_str()).style(Style::default().fg(Color::Green)),
-                Cell::from(format!("{}°C", info.temperature))
-                    .style(Style::default().fg(Color::Red)),
-                Cell::from(format!("{}%", info.utilization))
-                    .style(Style::default().fg(Color::Magenta)),
-                Cell::from(format!(
-                    "{}/{}",
-                    format_memory_size(info.memory_used),
-                    format_memory_size(info.memory_total)
-                ))
-                .style(Style::default().fg(Color::Blue)),
-                Cell::from(format!("{}/{}W", info.power_usage, info.power_limit))
-                    .style(Style::default().fg(Color::Yellow)),
-                Cell::from(format!("{}MHz", info.clock_freq))
-                    .style(Style::default().fg(Color::LightCyan)),
-            ];
-            Row::new(cells)
+        .zip(colors.iter().copied())
+        .map(|(header, color)| {
+            Cell::from(*header).style(Style::default().fg(color).add_modifier(Modifier::BOLD))
         })
         .collect();
+    header_cells.push(Cell::from("Status").style(Style::default().add_modifier(Modifier::BOLD)));
 
-    let table = Table::new(
-        rows,
-        &[
-            Constraint::Length(index_width as u16),
-            Constraint::Length(name_width as u16),
-            Constraint::Length(temp_width as u16),
-            Constraint::Length(util_width as u16),
-            Constraint::Length(memory_width as u16),
-            Constraint::Length(power_width as u16),
-            Constraint::Length(clock_width as u16),
-        ],
-    )
-    .header(Row::new(vec![
-        Cell::from("GPU").style(
-            Style::default()
-                .fg(Color::Cyan)
-                .add_modifier(Modifier::BOLD),
-        ),
-        Cell::from("Name").style(
-            Style::default()
-                .fg(Color::Green)
-                .add_modifier(Modifier::BOLD),
-        ),
-        Cell::from("Temp").style(Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
-        Cell::from("Util").style(
-            Style::default()
-                .fg(Color::Magenta)
-                .add_modifier(Modifier::BOLD),
-        ),
-        Cell::from("Memory").style(
-            Style::default()
-                .fg(Color::Blue)
-                .add_modifier(Modifier::BOLD),
-        ),
-        Cell::from("Power").style(
-            Style::default()
-                .fg(Color::Yellow)
-                .add_modifier(Modifier::BOLD),
-        ),
-        Cell::from("Clock").style(
-            Style::default()
-                .fg(Color::LightCyan)
-                .add_modifier(Modifier::BOLD),
-        ),
-    ]))
-    .widths(&[
-        Constraint::Length(index_width as u16),
-        Constraint::Length(name_width as u16),
-        Constraint::Length(temp_width as u16),
-        Constraint::Length(util_width as u16),
-        Constraint::Length(memory_width as u16),
-        Constraint::Length(power_width as u16),
-        Constraint::Length(clock_width as u16),
-    ])
-    .column_spacing(1);
+    let table = Table::new(rows, widths)
+        .header(Row::new(header_cells))
+        .column_spacing(1);
 
     f.render_widget(table, gpu_area);
 }
-pub fn render_process_list(f: &mut Frame, area: Rect, app_state: &AppState) {
+/// Title of the process list, summarizing the active filter and grouping.
+fn process_list_title(app_state: &AppState, shown: usize) -> String {
+    let filter = &app_state.filter;
+    let mut parts = Vec::new();
+    if filter.is_active() {
+        let total: usize = app_state
+            .gpu_infos
+            .iter()
+            .map(|gpu| gpu.processes.len())
+            .sum();
+        parts.push(format!("{} of {}", shown, total));
+    }
+    if !filter.pattern.is_empty() {
+        parts.push(format!("/{}/", filter.pattern));
+    }
+    if filter.only_mine() {
+        parts.push("mine".to_string());
+    }
+    if filter.only_compute {
+        parts.push("compute".to_string());
+    }
+    if app_state.grouping != Grouping::None {
+        parts.push(format!("by {}", app_state.grouping.name()));
+    }
+    if parts.is_empty() {
+        return "GPU Processes".to_string();
+    }
+    format!("GPU Processes ({})", parts.join(" | "))
+}
+
+pub fn render_process_list(f: &mut Frame, area: Rect, app_state: &mut AppState) {
     let layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
@@ -189,102 +164,135 @@ pub fn render_process_list(f: &mut Frame, area: Rect, app_state: &AppState) {
     let main_area = layout[0];
     let footer_area = layout[1];
 
+    let table_rows = app_state.table_rows();
+    let row_count = table_rows.len();
+    let shown = app_state.visible_processes().len();
     let block = Block::default()
         .borders(Borders::ALL)
-        .title("GPU Processes");
+        .title(process_list_title(app_state, shown));
     f.render_widget(block.clone(), main_area);
     let process_area = block.inner(main_area);
 
-    let mut all_processes = Vec::new();
-    for (gpu_index, gpu_info) in app_state.gpu_infos.iter().enumerate() {
-        for process in &gpu_info.processes {
-            all_processes.push((gpu_index, process));
-        }
-    }
-
-    all_processes.sort_by(|a, b| b.1.used_gpu_memory.cmp(&a.1.used_gpu_memory));
+    let columns = app_state.process_columns.clone();
+    let memory_metric = app_state.memory_metric;
+    // Elapsed times are relative to the newest sample so replays stay accurate
+    let now_ms = app_state.history.latest_ms().unwrap_or_else(unix_time_ms);
 
-    let rows: Vec<Row> = all_processes
+    let rows: Vec<Row> = table_rows
         .iter()
-        .enumerate()
-        .map(|(index, (gpu_index, process))| {
-            let style = if index == app_state.selected_process {
-                Style::default().bg(Color::DarkGray)
-            } else {
-                Style::default()
+        .map(|row| {
+            let selected = app_state.selected.as_ref() == Some(&row.key());
+            // A group counts as marked once all of its members are
+            let marked = row
+                .processes()
+                .iter()
+                .all(|&(gpu, p)| app_state.marked_processes.contains(&(gpu, p.pid)));
+            let style = match (selected, marked) {
+                (true, true) => Style::default().bg(Color::LightBlue),
+                (false, true) => Style::default().bg(Color::Blue),
+                (true, false) => Style::default().bg(Color::DarkGray),
+                (false, false) => Style::default(),
             };
 
-            Row::new(vec![
-                Cell::from(gpu_index.to_string()).style(style.fg(Color::Cyan)),
-                Cell::from(process.pid.to_string()).style(style.fg(Color::Yellow)),
-                Cell::from(format_memory_size(process.used_gpu_memory))
-                    .style(style.fg(Color::Green)),
-                Cell::from(format!("{:.1}%", process.cpu_usage)).style(style.fg(Color::Magenta)),
-                Cell::from(format_memory_size(process.memory_usage)).style(style.fg(Color::Blue)),
-                Cell::from(process.username.as_str()).style(style.fg(Color::Red)),
-                Cell::from(process.command.as_str()).style(style),
-            ])
+            match row {
+                TableRow::Group(group) => Row::new(columns.iter().map(|&column| {
+                    Cell::from(group.value(column, memory_metric))
+                        .style(style.fg(column.color()).add_modifier(Modifier::BOLD))
+                })),
+                &TableRow::Process {
+                    gpu_index,
+                    process,
+                    branch,
+                } => Row::new(columns.iter().map(|&column| {
+                    let mut text = column.value(gpu_index, process, memory_metric, now_ms);
+                    if let (ProcessColumn::Command, Some(branch)) = (column, branch) {
+                        text.insert_str(0, branch.prefix());
+                    }
+                    // Foreign processes can't be signalled, so they are dimmed
+                    let color = if process.foreign {
+                        Color::DarkGray
+                    } else {
+                        column.color()
+                    };
+                    Cell::from(text).style(style.fg(color))
+                })),
+            }
         })
         .collect();
 
-    let table = Table::new(
-        rows,
-        &[
-            Constraint::Length(3),
-            Constraint::Length(7),
-            Constraint::Length(8),
-            Constraint::Length(6),
-            Constraint::Length(8),
-            Constraint::Length(15),
-            Constraint::Percentage(100),
-        ],
-    )
-    .header(Row::new(vec![
-        Cell::from("GPU").style(
-            Style::default()
-                .fg(Color::Cyan)
-                .add_modifier(Modifier::BOLD),
-        ),
-        Cell::from("PID").style(
-            Style::default()
-                .fg(Color::Yellow)
-                .add_modifier(Modifier::BOLD),
-        ),
-        Cell::from("GPU Mem").style(
-            Style::default()
-                .fg(Color::Green)
-                .add_modifier(Modifier::BOLD),
-        ),
-        Cell::from("CPU").style(
-            Style::default()
-                .fg(Color::Magenta)
-                .add_modifier(Modifier::BOLD),
-        ),
-        Cell::from("Mem").style(
+    let header_cells = columns.iter().map(|&column| {
+        let header = column.header(memory_metric);
+        let header = if column == app_state.sort_column {
+            let arrow = if app_state.sort_descending {
+                '▼'
+            } else {
+                '▲'
+            };
+            format!("{}{}", header, arrow)
+        } else {
+            header.to_string()
+        };
+        Cell::from(header).style(
             Style::default()
-                .fg(Color::Blue)
+                .fg(column.color())
                 .add_modifier(Modifier::BOLD),
-        ),
-        Cell::from("User").style(Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
-        Cell::from("Command").style(Style::default().add_modifier(Modifier::BOLD)),
-    ]))
-    .column_spacing(1);
+        )
+    });
 
-    if let Some(error_msg) = &app_state.error_message {
-        let error_text = textwrap::wrap(error_msg, process_area.width as usize - 2);
-        let error_paragraph = Paragraph::new(error_text.join("\n"))
-            .style(Style::default().fg(Color::Red))
-            .block(Block::default().borders(Borders::ALL).title("Error"));
-        let error_area = Rect {
-            x: process_area.x,
-            y: process_area.y + process_area.height - 3,
-            width: process_area.width,
-            height: 3,
+    let table = Table::new(rows, columns.iter().map(|column| column.width()))
+        .header(Row::new(header_cells))
+        .column_spacing(1);
+
+    // Errors take precedence over the outcome of the last action
+    let error = app_state
+        .error_message
+        .as_ref()
+        .or(app_state.collection_error.as_ref());
+    let message = match (error, &app_state.status_message) {
+        (Some(error), _) => Some((error, "Error", Color::Red)),
+        (None, Some(status)) => Some((status, "Status", Color::Green)),
+        (None, None) => None,
+    };
+    let mut table_area = process_area;
+    if let Some((text, title, color)) = message {
+        let message_text = textwrap::wrap(text, process_area.width.saturating_sub(2) as usize);
+        let message_paragraph = Paragraph::new(message_text.join("\n"))
+            .style(Style::default().fg(color))
+            .block(Block::default().borders(Borders::ALL).title(title));
+        // Up to four lines of text, so batch summaries stay readable
+        let height = (message_text.len().min(4) as u16 + 2).min(process_area.height);
+        let message_area = Rect {
+            y: process_area.y + process_area.height - height,
+            height,
+            ..process_area
         };
-        f.render_widget(error_paragraph, error_area);
+        table_area.height -= height;
+        f.render_widget(message_paragraph, message_area);
     }
 
-    f.render_widget(table, process_area);
+    // The table scrolls to keep the selection in view
+    app_state.process_page_size = table_area.height.saturating_sub(1) as usize;
+    let selected_index = app_state.selected_index();
+    app_state.process_table.select(selected_index);
+    f.render_stateful_widget(table, table_area, &mut app_state.process_table);
+
+    if row_count > app_state.process_page_size {
+        let mut scrollbar_state = ScrollbarState::new(row_count)
+            .position(selected_index.unwrap_or(app_state.process_table.offset()));
+        // Drawn over the right border, next to the rows below the header
+        let scrollbar_area = Rect {
+            y: table_area.y + 1,
+            height: table_area.height.saturating_sub(1),
+            ..main_area
+        };
+        f.render_stateful_widget(
+            Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                .begin_symbol(None)
+                .end_symbol(None),
+            scrollbar_area,
+            &mut scrollbar_state,
+        );
+    }
     // Render the footer
-    render_footer(f, footer_area, &app_state);
+    render_footer(f, footer_area, app_state);
 }
@@ -0,0 +1,22 @@
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use ratatui::backend::CrosstermBackend;
+use ratatui::Terminal;
+use std::io::{stdout, Stdout};
+
+pub type Tui = Terminal<CrosstermBackend<Stdout>>;
+
+pub fn setup_terminal() -> std::io::Result<Tui> {
+    let mut stdout = stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    enable_raw_mode()?;
+    Terminal::new(CrosstermBackend::new(stdout))
+}
+
+pub fn restore_terminal(terminal: &mut Tui) -> std::io::Result<()> {
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()
+}
@@ -1,5 +1,13 @@
 use crate::app_state::AppState;
 use crate::gpu::info::GpuInfo;
+use crate::history::Metric;
+use crate::session::unix_time_ms;
+use crate::ui::columns::{pod_name, GpuColumn};
+use crate::ui::details::DetailPane;
+use crate::ui::kill::{KillDialog, SIGNALS};
+use crate::utils::formatting::{
+    format_duration, format_memory_size, format_or_na, format_power, format_timestamp,
+};
 use ratatui::layout::Rect;
 use ratatui::prelude::*;
 use ratatui::widgets::*;
@@ -17,23 +25,17 @@ pub fn render_gpu_graphs(f: &mut Frame, area: Rect, app_state: &AppState) {
 }
 pub fn render_gpu_bar_charts(f: &mut Frame, area: Rect, app_state: &AppState) {
     let gpu_count = app_state.gpu_infos.len();
+    // Nothing to draw before the first sample or without GPUs
+    let Some(percentage) = 100usize.checked_div(gpu_count) else {
+        return;
+    };
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints(vec![
-            Constraint::Percentage((100 / gpu_count) as u16);
-            gpu_count
-        ])
+        .constraints(vec![Constraint::Percentage(percentage as u16); gpu_count])
         .split(area);
 
     for (index, gpu_info) in app_state.gpu_infos.iter().enumerate() {
-        let gpu_area = chunks[index];
-        let gpu_chunks = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
-            .split(gpu_area);
-
-        render_power_bar(f, gpu_chunks[0], gpu_info, index);
-        render_utilization_bar(f, gpu_chunks[1], gpu_info, index);
+        render_gpu_bars(f, chunks[index], app_state, gpu_info, index);
     }
 }
 pub fn render_tabbed_gpu_graphs(f: &mut Frame, area: Rect, app_state: &AppState) {
@@ -61,24 +63,47 @@ pub fn render_tabbed_gpu_graphs(f: &mut Frame, area: Rect, app_state: &AppState)
     f.render_widget(tabs, chunks[0]);
 
     // Render graphs for the selected GPU
-    if let Some(_gpu_info) = app_state.gpu_infos.get(app_state.selected_gpu_tab) {
-        let gpu_chunks = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
-            .split(chunks[1]);
-
-        render_power_graph(f, gpu_chunks[0], app_state, app_state.selected_gpu_tab);
-        render_utilization_graph(f, gpu_chunks[1], app_state, app_state.selected_gpu_tab);
+    if app_state.selected_gpu_tab < app_state.gpu_infos.len() {
+        render_gpu_graph_pair(f, chunks[1], app_state, app_state.selected_gpu_tab);
     }
 }
 
 pub fn render_footer(f: &mut Frame, area: Rect, app_state: &AppState) {
-    let footer_text = if app_state.use_tabbed_graphs {
-        "↑↓: nav processes | ←→: switch GPU tabs | x: kill process | d: default mode | b: bar mode | q: quit"
+    let filter = &app_state.filter;
+    if filter.editing {
+        // The filter prompt takes over the footer while it has focus
+        let mut spans = vec![Span::raw(format!("/{}", filter.pattern))];
+        match &filter.error {
+            Some(_) => spans.push(Span::styled(
+                "  invalid regex",
+                Style::default().fg(Color::Red),
+            )),
+            None => spans.push(Span::styled(
+                "  enter: keep | esc: clear",
+                Style::default().fg(Color::Gray),
+            )),
+        }
+        f.render_widget(Paragraph::new(Line::from(spans)), area);
+        return;
+    }
+
+    let view_keys = if app_state.use_tabbed_graphs {
+        "←→: GPU tabs | d: default | b: bar"
     } else if app_state.use_bar_charts {
-        "↑↓: nav processes | x: kill process | d: default mode | t: tabbed mode | q: quit"
+        "d: default | t: tabbed"
     } else {
-        "↑↓: nav processes | x: kill process | b: bar mode | t: tabbed mode | q: quit"
+        "b: bar | t: tabbed"
+    };
+    let marked = match app_state.marked_processes.len() {
+        0 => String::new(),
+        count => format!("{} marked | esc: unmark | ", count),
+    };
+    let footer_text = match &app_state.replay_status {
+        Some(status) => status.clone(),
+        None => format!(
+            "{}↑↓: nav | enter: details | x: signal | space/A/U/I: mark | /: filter | u/c: mine/compute | T/z: group/fold | {} | v/V: series | s/S: sort | m: memory | o: columns | q: quit",
+            marked, view_keys
+        ),
     };
 
     let footer = Paragraph::new(footer_text)
@@ -89,24 +114,14 @@ pub fn render_footer(f: &mut Frame, area: Rect, app_state: &AppState) {
 
 pub fn render_all_gpu_graphs(f: &mut Frame, area: Rect, app_state: &AppState) {
     let gpu_count = app_state.gpu_infos.len();
-    if gpu_count > 0 {
+    if let Some(percentage) = 100usize.checked_div(gpu_count) {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
-            .constraints(vec![
-                Constraint::Percentage((100 / gpu_count) as u16);
-                gpu_count
-            ])
+            .constraints(vec![Constraint::Percentage(percentage as u16); gpu_count])
             .split(area);
 
-        for (index, _) in app_state.gpu_infos.iter().enumerate() {
-            let gpu_area = chunks[index];
-            let gpu_chunks = Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
-                .split(gpu_area);
-
-            render_power_graph(f, gpu_chunks[0], app_state, index);
-            render_utilization_graph(f, gpu_chunks[1], app_state, index);
+        for index in 0..gpu_count {
+            render_gpu_graph_pair(f, chunks[index], app_state, index);
         }
     } else {
         // Display a message when no GPUs are found
@@ -118,132 +133,494 @@ pub fn render_all_gpu_graphs(f: &mut Frame, area: Rect, app_state: &AppState) {
     }
 }
 
-pub fn render_power_bar(f: &mut Frame, area: Rect, gpu_info: &GpuInfo, gpu_index: usize) {
-    let power_percentage = cmp::min(
-        100,
-        ((gpu_info.power_usage as f64 / gpu_info.power_limit as f64) * 100.0) as u16,
-    );
-    let power_bar = Gauge::default()
-        .block(
-            Block::default()
-                .title(format!("GPU {} Power", gpu_index))
-                .borders(Borders::ALL),
-        )
-        .gauge_style(Style::default().fg(Color::Yellow))
-        .percent(power_percentage)
-        .label(format!(
-            "{}/{}W",
-            gpu_info.power_usage, gpu_info.power_limit
-        ));
-    f.render_widget(power_bar, area);
+pub fn render_gpu_bars(
+    f: &mut Frame,
+    area: Rect,
+    app_state: &AppState,
+    gpu_info: &GpuInfo,
+    gpu_index: usize,
+) {
+    let gpu_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(area);
+
+    for (chunk, metric) in gpu_chunks.iter().zip(app_state.graph_metrics) {
+        render_metric_bar(f, *chunk, gpu_info, gpu_index, metric);
+    }
 }
 
-pub fn render_utilization_bar(f: &mut Frame, area: Rect, gpu_info: &GpuInfo, gpu_index: usize) {
-    let util_percentage = cmp::min(100, gpu_info.utilization as u16);
-    let util_bar = Gauge::default()
+pub fn render_metric_bar(
+    f: &mut Frame,
+    area: Rect,
+    gpu_info: &GpuInfo,
+    gpu_index: usize,
+    metric: Metric,
+) {
+    let value = metric.value(gpu_info);
+    // Metrics without a natural limit, like temperature, are shown out of 100
+    let limit = metric.limit(gpu_info).unwrap_or(100.0);
+    let percentage = match value {
+        Some(value) if limit > 0.0 => cmp::min(100, (value / limit * 100.0) as u16),
+        _ => 0,
+    };
+    let label = match metric {
+        Metric::Power => format_power(gpu_info.power_usage, gpu_info.power_limit),
+        _ => format_or_na(value, |v| format!("{:.0}{}", v, metric.unit())),
+    };
+    let bar = Gauge::default()
         .block(
             Block::default()
-                .title(format!("GPU {} Utilization", gpu_index))
+                .title(format!("GPU {} {}", gpu_index, metric.name()))
                 .borders(Borders::ALL),
         )
-        .gauge_style(Style::default().fg(Color::Magenta))
-        .percent(util_percentage)
-        .label(format!("{}%", gpu_info.utilization));
-    f.render_widget(util_bar, area);
+        .gauge_style(Style::default().fg(metric_color(metric)))
+        .percent(percentage)
+        .label(label);
+    f.render_widget(bar, area);
 }
 
-pub fn render_power_graph(f: &mut Frame, area: Rect, app_state: &AppState, gpu_index: usize) {
+pub fn render_gpu_graph_pair(f: &mut Frame, area: Rect, app_state: &AppState, gpu_index: usize) {
+    let gpu_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(area);
+
+    for (chunk, metric) in gpu_chunks.iter().zip(app_state.graph_metrics) {
+        render_metric_graph(f, *chunk, app_state, gpu_index, metric);
+    }
+}
+
+pub fn render_metric_graph(
+    f: &mut Frame,
+    area: Rect,
+    app_state: &AppState,
+    gpu_index: usize,
+    metric: Metric,
+) {
     let gpu_info = &app_state.gpu_infos[gpu_index];
-    let power_data: Vec<(f64, f64)> = app_state.power_history[gpu_index]
-        .iter()
-        .enumerate()
-        .map(|(i, &v)| (i as f64, v as f64))
-        .collect();
+    let data = graph_points(app_state, gpu_index, metric);
+    let axis_title = format!("{} ({})", metric.name(), metric.unit());
+
+    let y_axis = Axis::default()
+        .title(axis_title.clone())
+        .style(Style::default().fg(Color::Gray));
+    let y_axis = if metric.unit() == "%" {
+        y_axis.bounds([0.0, 100.0]).labels(
+            ["0", "25", "50", "75", "100"]
+                .iter()
+                .map(|&s| s.to_string())
+                .collect::<Vec<String>>(),
+        )
+    } else {
+        // Without a reported limit, scale the graph to the highest recorded value
+        let limit = metric
+            .limit(gpu_info)
+            .unwrap_or_else(|| data.iter().map(|&(_, v)| v).fold(100.0, f64::max));
+        y_axis.bounds([0.0, limit * 1.1]).labels(vec![
+            format!("{:.0}", 0.0),
+            format!("{:.0}", limit / 2.0),
+            format!("{:.0}", limit),
+        ])
+    };
 
-    let power_dataset = Dataset::default()
-        .name("Power (W)")
+    let dataset = Dataset::default()
+        .name(axis_title)
         .marker(symbols::Marker::Braille)
         .graph_type(ratatui::widgets::GraphType::Line)
-        .style(Style::default().fg(Color::Yellow))
-        .data(&power_data);
+        .style(Style::default().fg(metric_color(metric)))
+        .data(&data);
 
-    let power_chart = Chart::new(vec![power_dataset])
+    let chart = Chart::new(vec![dataset])
         .block(
             Block::default()
-                .title(format!("GPU {} Power", gpu_index))
+                .title(graph_title(gpu_index, metric, gpu_info))
                 .borders(Borders::ALL),
         )
-        .x_axis(
-            Axis::default()
-                .title("Time (s)")
-                .style(Style::default().fg(Color::Gray))
-                .bounds([0.0, 60.0])
-                .labels(
-                    ["0", "15", "30", "45", "60"]
-                        .iter()
-                        .map(|&s| s.to_string())
-                        .collect::<Vec<String>>(),
-                ),
-        )
-        .y_axis(
-            Axis::default()
-                .title("Power (W)")
-                .style(Style::default().fg(Color::Gray))
-                .bounds([0.0, gpu_info.power_limit as f64 * 1.1])
-                .labels(vec![
-                    format!("{:.0}", 0.0),
-                    format!("{:.0}", gpu_info.power_limit as f64 / 2.0),
-                    format!("{:.0}", gpu_info.power_limit as f64),
-                ]),
-        );
+        .x_axis(time_axis(app_state))
+        .y_axis(y_axis);
 
-    f.render_widget(power_chart, area);
+    f.render_widget(chart, area);
 }
 
-pub fn render_utilization_graph(f: &mut Frame, area: Rect, app_state: &AppState, gpu_index: usize) {
-    let util_data: Vec<(f64, f64)> = app_state.utilization_history[gpu_index]
+fn metric_color(metric: Metric) -> Color {
+    match metric {
+        Metric::Power => Color::Yellow,
+        Metric::Utilization => Color::Magenta,
+        Metric::MemoryUtilization => Color::LightMagenta,
+        Metric::Temperature => Color::Red,
+        Metric::FanSpeed => Color::LightRed,
+        Metric::Clock | Metric::SmClock => Color::LightCyan,
+        Metric::MemoryClock => Color::Cyan,
+        Metric::PcieRx => Color::LightBlue,
+        Metric::PcieTx => Color::Blue,
+    }
+}
+
+/// Popup listing the optional GPU table columns with their visibility.
+pub fn render_column_picker(f: &mut Frame, app_state: &AppState, cursor: usize) {
+    let items: Vec<ListItem> = GpuColumn::ALL
         .iter()
-        .enumerate()
-        .map(|(i, &v)| (i as f64, v as f64))
+        .map(|column| {
+            let mark = if app_state.gpu_columns.contains(column) {
+                "[x]"
+            } else {
+                "[ ]"
+            };
+            ListItem::new(format!("{} {}", mark, column.header()))
+                .style(Style::default().fg(column.color()))
+        })
         .collect();
 
-    let util_dataset = Dataset::default()
-        .name("Utilization (%)")
-        .marker(symbols::Marker::Braille)
-        .graph_type(ratatui::widgets::GraphType::Line)
-        .style(Style::default().fg(Color::Magenta))
-        .data(&util_data);
-
-    let util_chart = Chart::new(vec![util_dataset])
+    let height = GpuColumn::ALL.len() as u16 + 2;
+    let area = centered_rect(f.area(), 34, height);
+    let list = List::new(items)
         .block(
             Block::default()
-                .title(format!("GPU {} Utilization", gpu_index))
-                .borders(Borders::ALL),
+                .borders(Borders::ALL)
+                .title("GPU Columns")
+                .title_bottom("space: toggle | esc: close"),
         )
-        .x_axis(
-            Axis::default()
-                .title("Time (s)")
-                .style(Style::default().fg(Color::Gray))
-                .bounds([0.0, 60.0])
-                .labels(
-                    ["0", "15", "30", "45", "60"]
-                        .iter()
-                        .map(|&s| s.to_string())
-                        .collect::<Vec<String>>(),
-                ),
+        .highlight_style(Style::default().bg(Color::DarkGray));
+    let mut state = ListState::default().with_selected(Some(cursor));
+
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+// Marked processes listed by name in the kill dialog.
+const MAX_LISTED_TARGETS: usize = 8;
+
+/// Confirmation popup for sending a signal to the selected or marked
+/// processes.
+pub fn render_kill_dialog(f: &mut Frame, app_state: &AppState, dialog: &KillDialog) {
+    let label = |text: &'static str| Span::styled(text, Style::default().fg(Color::Gray));
+
+    let mut lines = match dialog.targets.as_slice() {
+        [target] => {
+            let (gpu_index, pid) = target.key;
+            vec![
+                Line::from(vec![label("PID:     "), Span::raw(pid.to_string())]),
+                Line::from(vec![
+                    label("User:    "),
+                    Span::raw(target.username.as_str()),
+                ]),
+                Line::from(vec![
+                    label("GPU:     "),
+                    Span::raw(format!(
+                        "{} ({} GPU memory)",
+                        gpu_index,
+                        format_or_na(target.used_gpu_memory, format_memory_size)
+                    )),
+                ]),
+                Line::from(vec![label("Command: "), Span::raw(target.command.as_str())]),
+            ]
+        }
+        targets => {
+            let total_memory = targets.iter().filter_map(|t| t.used_gpu_memory).sum();
+            let mut lines = vec![Line::from(vec![
+                label("Targets: "),
+                Span::raw(format!(
+                    "{} marked processes ({} GPU memory)",
+                    targets.len(),
+                    format_memory_size(total_memory)
+                )),
+            ])];
+            // List the first few so the dialog fits on small terminals
+            for target in targets.iter().take(MAX_LISTED_TARGETS) {
+                let (gpu_index, pid) = target.key;
+                lines.push(Line::from(format!(
+                    "  GPU {} {:>7} {:<10} {}",
+                    gpu_index, pid, target.username, target.command
+                )));
+            }
+            if targets.len() > MAX_LISTED_TARGETS {
+                lines.push(Line::styled(
+                    format!("  ... and {} more", targets.len() - MAX_LISTED_TARGETS),
+                    Style::default().fg(Color::Gray),
+                ));
+            }
+            lines
+        }
+    };
+    lines.push(Line::default());
+    for (index, (signal, description)) in SIGNALS.iter().enumerate() {
+        let style = if index == dialog.signal {
+            Style::default().fg(Color::Yellow).bg(Color::DarkGray)
+        } else {
+            Style::default()
+        };
+        lines.push(Line::styled(
+            format!("{:<8} {}", signal.as_str(), description),
+            style,
+        ));
+    }
+    lines.push(Line::default());
+    lines.push(Line::from(format!(
+        "[{}] Escalate to SIGKILL if GPU memory is still held after {}s",
+        if dialog.escalate { "x" } else { " " },
+        app_state.escalation_timeout.as_secs()
+    )));
+
+    let area = centered_rect(f.area(), 72, lines.len() as u16 + 2);
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Red))
+            .title("Send Signal")
+            .title_bottom("↑↓: signal | e: escalation | enter: send | esc: cancel"),
+    );
+
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+}
+
+/// Popup with everything known about one process, opened with Enter.
+pub fn render_detail_pane(f: &mut Frame, app_state: &AppState, pane: &DetailPane) {
+    let label = |text: &'static str| Span::styled(text, Style::default().fg(Color::Gray));
+    let heading = |text: &'static str| {
+        Line::styled(
+            text,
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
         )
-        .y_axis(
-            Axis::default()
-                .title("Utilization (%)")
-                .style(Style::default().fg(Color::Gray))
-                .bounds([0.0, 100.0])
-                .labels(
-                    ["0", "25", "50", "75", "100"]
-                        .iter()
-                        .map(|&s| s.to_string())
-                        .collect::<Vec<String>>(),
-                ),
-        );
-
-    f.render_widget(util_chart, area);
+    };
+    let process = &pane.process;
+    let (gpu_index, pid) = pane.key;
+    let now_ms = app_state.history.latest_ms().unwrap_or_else(unix_time_ms);
+
+    let mut lines = vec![
+        Line::from(vec![label("PID:         "), Span::raw(pid.to_string())]),
+        Line::from(vec![
+            label("User:        "),
+            Span::raw(process.username.as_str()),
+        ]),
+        Line::from(vec![
+            label("GPU:         "),
+            Span::raw(format!(
+                "{} ({} GPU memory)",
+                gpu_index,
+                format_or_na(process.used_gpu_memory, format_memory_size)
+            )),
+        ]),
+        Line::from(vec![
+            label("GPU usage:   "),
+            Span::raw(format_or_na(process.utilization, |u| {
+                format!(
+                    "SM {}% | Mem {}% | Enc {}% | Dec {}%",
+                    u.sm, u.memory, u.encoder, u.decoder
+                )
+            })),
+        ]),
+        Line::from(vec![
+            label("Command:     "),
+            Span::raw(process.command.as_str()),
+        ]),
+    ];
+    if let Some(details) = &pane.details {
+        lines.push(Line::from(vec![
+            label("Executable:  "),
+            Span::raw(details.exe.as_deref().unwrap_or("N/A")),
+        ]));
+        lines.push(Line::from(vec![
+            label("Working dir: "),
+            Span::raw(details.cwd.as_deref().unwrap_or("N/A")),
+        ]));
+    }
+    lines.extend([
+        Line::from(vec![
+            label("Started:     "),
+            Span::raw(format_or_na(process.start_time_ms, |start| {
+                format!(
+                    "{} ({} ago)",
+                    format_timestamp(start, now_ms.saturating_sub(start)),
+                    format_duration(now_ms.saturating_sub(start))
+                )
+            })),
+        ]),
+        Line::from(vec![
+            label("Threads:     "),
+            Span::raw(format_or_na(process.threads, |t| t.to_string())),
+        ]),
+        Line::from(vec![
+            label("State:       "),
+            Span::raw(format_or_na(process.state, |s| s.to_string())),
+        ]),
+        Line::from(vec![
+            label("Nice:        "),
+            Span::raw(format_or_na(process.nice, |n| n.to_string())),
+        ]),
+    ]);
+    if let Some(container) = &process.container {
+        lines.push(Line::from(vec![
+            label("Container:   "),
+            Span::raw(container.display_name()),
+        ]));
+    }
+    if let Some(pod) = pod_name(process) {
+        lines.push(Line::from(vec![label("Pod:         "), Span::raw(pod)]));
+    }
+    if let Some(job) = &process.slurm_job {
+        lines.push(Line::from(vec![
+            label("Slurm job:   "),
+            Span::raw(match &job.step {
+                Some(step) => format!("{} step {}", job.display_id(), step),
+                None => job.display_id(),
+            }),
+        ]));
+    }
+
+    match &pane.details {
+        Some(details) => {
+            lines.push(Line::default());
+            lines.push(heading("GPU environment"));
+            if details.environment.is_empty() {
+                lines.push(Line::styled(
+                    "  none or not readable",
+                    Style::default().fg(Color::Gray),
+                ));
+            }
+            for (name, value) in &details.environment {
+                lines.push(Line::from(format!("  {}={}", name, value)));
+            }
+            lines.push(Line::default());
+            lines.push(heading("NVIDIA device files"));
+            if details.device_handles.is_empty() {
+                lines.push(Line::styled(
+                    "  none or not readable",
+                    Style::default().fg(Color::Gray),
+                ));
+            }
+            for (fd, path) in &details.device_handles {
+                lines.push(Line::from(format!("  fd {:<4} {}", fd, path)));
+            }
+            lines.push(Line::default());
+            lines.push(heading("Libraries"));
+            if details.libraries.is_empty() {
+                lines.push(Line::styled(
+                    "  none or not readable",
+                    Style::default().fg(Color::Gray),
+                ));
+            }
+            for (name, version) in &details.libraries {
+                lines.push(Line::from(format!(
+                    "  {:<12} {}",
+                    name,
+                    version.as_deref().unwrap_or("unknown version")
+                )));
+            }
+        }
+        None => {
+            lines.push(Line::default());
+            lines.push(Line::styled(
+                if process.foreign {
+                    "Not in nviwatch's PID namespace, so /proc has nothing more to show"
+                } else {
+                    "Environment, device files and libraries are only read from live processes"
+                },
+                Style::default().fg(Color::Gray),
+            ));
+        }
+    }
+
+    let area = f.area();
+    let area = centered_rect(
+        area,
+        area.width.saturating_sub(8),
+        area.height.saturating_sub(4),
+    );
+    let title = if pane.exited {
+        format!("Process {} (exited)", pid)
+    } else {
+        format!("Process {}", pid)
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .title_bottom("↑↓: scroll | esc: close");
+    let inner = block.inner(area);
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    // SM utilization of the last samples above the text, newest on the right
+    let mut text_area = inner;
+    if let Some(samples) = app_state.history.process_utilization(pane.key) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(4), Constraint::Min(0)])
+            .split(inner);
+        let skip = samples.len().saturating_sub(inner.width as usize);
+        let data: Vec<u64> = samples.iter().skip(skip).map(|u| u64::from(u.sm)).collect();
+        let sparkline = Sparkline::default()
+            .block(Block::default().title(Line::styled(
+                "SM utilization",
+                Style::default().fg(Color::Gray),
+            )))
+            .data(&data)
+            .max(100)
+            .style(Style::default().fg(Color::LightMagenta));
+        f.render_widget(sparkline, chunks[0]);
+        text_area = chunks[1];
+    }
+    let paragraph = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .scroll((pane.scroll, 0));
+    f.render_widget(paragraph, text_area);
+}
+
+/// A rectangle of at most `width` x `height` in the middle of `area`.
+pub fn centered_rect(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+/// Start and end of the time axis, in milliseconds since the epoch. The axis
+/// ends at the newest sample so replays show the recorded time.
+fn time_range(app_state: &AppState) -> (u64, u64) {
+    let end_ms = app_state.history.latest_ms().unwrap_or_else(unix_time_ms);
+    (end_ms.saturating_sub(app_state.history.window_ms()), end_ms)
+}
+
+/// Points of a metric with x in seconds from the start of the time axis.
+fn graph_points(app_state: &AppState, gpu_index: usize, metric: Metric) -> Vec<(f64, f64)> {
+    let (start_ms, end_ms) = time_range(app_state);
+    app_state
+        .history
+        .series(gpu_index, metric)
+        .map(|series| {
+            series
+                .points(start_ms, end_ms)
+                .into_iter()
+                .map(|(t, v)| ((t - start_ms) as f64 / 1000.0, v))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn time_axis(app_state: &AppState) -> Axis<'static> {
+    let (start_ms, end_ms) = time_range(app_state);
+    let window_ms = end_ms - start_ms;
+    let labels: Vec<String> = (0..5)
+        .map(|i| format_timestamp(start_ms + window_ms * i / 4, window_ms))
+        .collect();
+
+    Axis::default()
+        .title("Time")
+        .style(Style::default().fg(Color::Gray))
+        .bounds([0.0, window_ms as f64 / 1000.0])
+        .labels(labels)
+}
+
+fn graph_title(gpu_index: usize, metric: Metric, gpu_info: &GpuInfo) -> String {
+    match metric.value(gpu_info) {
+        Some(_) => format!("GPU {} {}", gpu_index, metric.name()),
+        None => format!("GPU {} {} (N/A)", gpu_index, metric.name()),
+    }
 }
@@ -0,0 +1,312 @@
+use crate::gpu::process::{Container, ContainerRuntime, Pod};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::time::Duration;
+
+const DOCKER_SOCKET: &str = "/var/run/docker.sock";
+const KUBELET_PODS_DIR: &str = "/var/lib/kubelet/pods";
+// Keeps a hung Docker daemon from stalling the collector
+const DOCKER_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Finds the container and pod a process runs in from its cgroup path, which
+/// looks like one of
+///
+/// - `/docker/<id>` or `/system.slice/docker-<id>.scope` (Docker)
+/// - `/kubepods/burstable/pod<uid>/<id>` (Kubernetes, cgroupfs driver)
+/// - `/kubepods.slice/.../kubepods-burstable-pod<uid>.slice/cri-containerd-<id>.scope`
+/// - `/system.slice/containerd.service/kubepods-pod<uid>.slice:cri-containerd:<id>`
+/// - `/machine.slice/libpod-<id>.scope` (Podman)
+pub fn parse_cgroup(path: &str) -> Option<Container> {
+    let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+    let (last, parents) = components.split_last()?;
+
+    let (runtime, id, pod_component) = match last.split(':').collect::<Vec<_>>()[..] {
+        // The systemd driver of containerd packs the pod slice, engine and
+        // container ID into one component
+        [slice, engine, id] => (runtime_of(engine), id, Some(slice)),
+        _ => {
+            let unit = last.strip_suffix(".scope").unwrap_or(last);
+            match unit.rsplit_once('-') {
+                Some((engine, id)) => (runtime_of(engine), id, None),
+                None => {
+                    let runtime = parents
+                        .last()
+                        .filter(|&&parent| parent == "docker")
+                        .map(|_| ContainerRuntime::Docker);
+                    (runtime, unit, None)
+                }
+            }
+        }
+    };
+    if !is_container_id(id) {
+        return None;
+    }
+
+    let pod = pod_component
+        .into_iter()
+        .chain(parents.iter().rev().copied())
+        .find_map(pod_uid)
+        .map(|uid| Pod {
+            uid,
+            name: None,
+            namespace: None,
+        });
+    // Only Kubernetes leaves the engine out of the path
+    if runtime.is_none() && pod.is_none() {
+        return None;
+    }
+
+    Some(Container {
+        id: id.to_string(),
+        runtime,
+        name: None,
+        pod,
+    })
+}
+
+fn runtime_of(engine: &str) -> Option<ContainerRuntime> {
+    match engine {
+        "docker" => Some(ContainerRuntime::Docker),
+        "cri-containerd" | "containerd" => Some(ContainerRuntime::Containerd),
+        "crio" => Some(ContainerRuntime::CriO),
+        "libpod" => Some(ContainerRuntime::Podman),
+        _ => None,
+    }
+}
+
+fn is_container_id(id: &str) -> bool {
+    id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Pod UID from a `pod<uid>` (cgroupfs) or `kubepods-<qos>-pod<uid>.slice`
+/// (systemd) component. The systemd driver writes the dashes of the UID as
+/// underscores.
+fn pod_uid(component: &str) -> Option<String> {
+    let uid = match component.strip_suffix(".slice") {
+        Some(slice) => slice.rsplit_once("-pod")?.1,
+        None => component.strip_prefix("pod")?,
+    };
+    let uid = uid.replace('_', "-");
+    let is_uid = uid.len() == 36 && uid.bytes().all(|b| b.is_ascii_hexdigit() || b == b'-');
+    is_uid.then_some(uid)
+}
+
+/// Resolves container and pod names, caching them so each container is
+/// looked up once while it has GPU processes.
+#[derive(Default)]
+pub struct ContainerResolver {
+    previous: HashMap<String, Container>,
+    current: HashMap<String, Container>,
+}
+
+impl ContainerResolver {
+    /// Fills in the names of a container parsed from a cgroup path.
+    pub fn resolve(&mut self, container: Container) -> Container {
+        if let Some(resolved) = self.current.get(&container.id) {
+            return resolved.clone();
+        }
+        let resolved = match self.previous.remove(&container.id) {
+            Some(resolved) => resolved,
+            None => resolve_names(container),
+        };
+        self.current.insert(resolved.id.clone(), resolved.clone());
+        resolved
+    }
+
+    /// Ends a refresh. Containers that were not seen in it are forgotten.
+    pub fn finish_refresh(&mut self) {
+        self.previous = std::mem::take(&mut self.current);
+    }
+}
+
+fn resolve_names(mut container: Container) -> Container {
+    let docker = match container.runtime {
+        Some(ContainerRuntime::Docker) | None => inspect_docker_container(&container.id),
+        _ => None,
+    };
+    if let Some(docker) = &docker {
+        container.name = docker["Name"]
+            .as_str()
+            .map(|name| name.trim_start_matches('/').to_string());
+    }
+    if let Some(pod) = &mut container.pod {
+        // Pods run by the dockershim carry their names as labels
+        let labels = docker.as_ref().map(|docker| &docker["Config"]["Labels"]);
+        let label = |key: &str| {
+            labels
+                .and_then(|labels| labels[key].as_str())
+                .map(String::from)
+        };
+        if let Some(name) = label("io.kubernetes.container.name") {
+            container.name = Some(name);
+        }
+        pod.name = label("io.kubernetes.pod.name").or_else(|| kubelet_pod_name(&pod.uid));
+        pod.namespace =
+            label("io.kubernetes.pod.namespace").or_else(|| kubelet_pod_namespace(&pod.uid));
+    }
+    container
+}
+
+/// Inspects a container through the Docker Engine API. Needs access to the
+/// socket, which usually means root or the `docker` group.
+fn inspect_docker_container(id: &str) -> Option<Value> {
+    let mut stream = UnixStream::connect(DOCKER_SOCKET).ok()?;
+    stream.set_read_timeout(Some(DOCKER_TIMEOUT)).ok()?;
+    stream.set_write_timeout(Some(DOCKER_TIMEOUT)).ok()?;
+    // HTTP/1.0 so the daemon closes the connection after the response
+    write!(
+        stream,
+        "GET /containers/{}/json HTTP/1.0\r\nHost: docker\r\n\r\n",
+        id
+    )
+    .ok()?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).ok()?;
+
+    let (head, body) = response.split_once("\r\n\r\n")?;
+    if !head.starts_with("HTTP/1.0 200") && !head.starts_with("HTTP/1.1 200") {
+        return None;
+    }
+    serde_json::from_str(body).ok()
+}
+
+/// Pod name from the hosts file the kubelet writes for the pod, where the
+/// pod's own hostname is the last entry before any host aliases. This is the
+/// pod name unless the pod sets `spec.hostname`.
+fn kubelet_pod_name(uid: &str) -> Option<String> {
+    let hosts = fs::read_to_string(Path::new(KUBELET_PODS_DIR).join(uid).join("etc-hosts")).ok()?;
+    hosts
+        .lines()
+        .take_while(|line| !line.starts_with("# Entries added by HostAliases"))
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .last()
+        .map(String::from)
+}
+
+/// Pod namespace from the service account token volume the kubelet projects
+/// into the pod.
+fn kubelet_pod_namespace(uid: &str) -> Option<String> {
+    let volumes = Path::new(KUBELET_PODS_DIR)
+        .join(uid)
+        .join("volumes/kubernetes.io~projected");
+    fs::read_dir(volumes)
+        .ok()?
+        .filter_map(Result::ok)
+        .find_map(|volume| fs::read_to_string(volume.path().join("namespace")).ok())
+        .map(|namespace| namespace.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "3f4e5d6c7b8a99aabbccddeeff00112233445566778899aabbccddeeff001122";
+    const POD_UID: &str = "a1b2c3d4-e5f6-4789-abcd-ef0123456789";
+
+    /// Parses the path of a `/proc/<pid>/cgroup` line, which is
+    /// `hierarchy-ID:controllers:path`.
+    fn parse_line(line: &str) -> Option<Container> {
+        parse_cgroup(line.splitn(3, ':').nth(2).unwrap())
+    }
+
+    #[test]
+    fn docker_cgroupfs_v1() {
+        let container = parse_line(&format!("12:memory:/docker/{}", ID)).unwrap();
+        assert_eq!(container.id, ID);
+        assert_eq!(container.runtime, Some(ContainerRuntime::Docker));
+        assert_eq!(container.pod, None);
+    }
+
+    #[test]
+    fn docker_systemd_v2() {
+        let container = parse_line(&format!("0::/system.slice/docker-{}.scope", ID)).unwrap();
+        assert_eq!(container.id, ID);
+        assert_eq!(container.runtime, Some(ContainerRuntime::Docker));
+    }
+
+    #[test]
+    fn kubernetes_cgroupfs_v1() {
+        let container = parse_line(&format!(
+            "4:devices:/kubepods/burstable/pod{}/{}",
+            POD_UID, ID
+        ))
+        .unwrap();
+        assert_eq!(container.id, ID);
+        assert_eq!(container.runtime, None);
+        assert_eq!(container.pod.unwrap().uid, POD_UID);
+    }
+
+    #[test]
+    fn kubernetes_containerd_systemd_v2() {
+        let container = parse_line(&format!(
+            "0::/kubepods.slice/kubepods-burstable.slice/kubepods-burstable-pod{}.slice/cri-containerd-{}.scope",
+            POD_UID.replace('-', "_"),
+            ID
+        ))
+        .unwrap();
+        assert_eq!(container.id, ID);
+        assert_eq!(container.runtime, Some(ContainerRuntime::Containerd));
+        assert_eq!(container.pod.unwrap().uid, POD_UID);
+    }
+
+    #[test]
+    fn kubernetes_containerd_packed_component() {
+        let container = parse_line(&format!(
+            "0::/system.slice/containerd.service/kubepods-besteffort-pod{}.slice:cri-containerd:{}",
+            POD_UID.replace('-', "_"),
+            ID
+        ))
+        .unwrap();
+        assert_eq!(container.id, ID);
+        assert_eq!(container.runtime, Some(ContainerRuntime::Containerd));
+        assert_eq!(container.pod.unwrap().uid, POD_UID);
+    }
+
+    #[test]
+    fn kubernetes_crio_v1() {
+        let container = parse_line(&format!(
+            "9:cpu,cpuacct:/kubepods.slice/kubepods-pod{}.slice/crio-{}.scope",
+            POD_UID.replace('-', "_"),
+            ID
+        ))
+        .unwrap();
+        assert_eq!(container.id, ID);
+        assert_eq!(container.runtime, Some(ContainerRuntime::CriO));
+        assert_eq!(container.pod.unwrap().uid, POD_UID);
+    }
+
+    #[test]
+    fn podman_v2() {
+        let container = parse_line(&format!("0::/machine.slice/libpod-{}.scope", ID)).unwrap();
+        assert_eq!(container.id, ID);
+        assert_eq!(container.runtime, Some(ContainerRuntime::Podman));
+        assert_eq!(container.pod, None);
+    }
+
+    #[test]
+    fn host_processes_are_not_containers() {
+        for line in [
+            "0::/user.slice/user-1000.slice/session-2.scope",
+            "0::/system.slice/sshd.service",
+            "0::/",
+            "12:memory:/docker",
+            // Not a 64 character container ID
+            "0::/system.slice/docker-3f4e5d6c7b8a.scope",
+            // A container ID without an engine or pod to tell what made it
+            &format!("0::/system.slice/{}", ID),
+        ] {
+            assert!(parse_line(line).is_none(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn short_id_is_shown_without_a_name() {
+        let container = parse_line(&format!("0::/docker/{}", ID)).unwrap();
+        assert_eq!(container.display_name(), &ID[..12]);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::gpu::process::ProcessType;
+use procfs::process::{all_processes, FDTarget, MMapPath, Process};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// Scanning every process is too costly to repeat at the refresh rate
+const SCAN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Finds GPU processes by their open `/dev/nvidia<N>` handles, for when NVML
+/// lists none or only PIDs of another namespace, as inside containers or with
+/// restricted NVML permissions. Their GPU memory is not known.
+#[derive(Default)]
+pub struct DeviceFileScanner {
+    // PIDs with the kind of context they hold, by device minor number
+    users: HashMap<u32, Vec<(u32, ProcessType)>>,
+    scanned_at: Option<Instant>,
+}
+
+impl DeviceFileScanner {
+    /// Processes with a device file of the GPU with minor number `minor` open,
+    /// as of the last scan.
+    pub fn users(&self, minor: u32) -> &[(u32, ProcessType)] {
+        self.users.get(&minor).map_or(&[], Vec::as_slice)
+    }
+
+    /// Rescans `/proc` if the last scan is older than `SCAN_INTERVAL`.
+    pub fn refresh(&mut self) {
+        if self
+            .scanned_at
+            .is_some_and(|at| at.elapsed() < SCAN_INTERVAL)
+        {
+            return;
+        }
+        self.scanned_at = Some(Instant::now());
+        self.users.clear();
+
+        let Ok(processes) = all_processes() else {
+            return;
+        };
+        let own_pid = std::process::id() as i32;
+        // Processes of other users are skipped unless running as root
+        for process in processes.filter_map(Result::ok) {
+            if process.pid == own_pid {
+                continue;
+            }
+            let minors = open_devices(&process);
+            if minors.is_empty() {
+                continue;
+            }
+            let Some(process_type) = process_type(&process) else {
+                continue;
+            };
+            for minor in minors {
+                self.users
+                    .entry(minor)
+                    .or_default()
+                    .push((process.pid as u32, process_type));
+            }
+        }
+    }
+}
+
+/// Minor numbers of the `/dev/nvidia<N>` devices a process has open.
+/// `/dev/nvidiactl` and `/dev/nvidia-uvm` are shared by all GPUs and left out.
+fn open_devices(process: &Process) -> Vec<u32> {
+    let Ok(fds) = process.fd() else {
+        return Vec::new();
+    };
+    let mut minors: Vec<u32> = fds
+        .filter_map(Result::ok)
+        .filter_map(|fd| match fd.target {
+            FDTarget::Path(path) => path.to_str()?.strip_prefix("/dev/nvidia")?.parse().ok(),
+            _ => None,
+        })
+        .collect();
+    minors.sort_unstable();
+    minors.dedup();
+    minors
+}
+
+/// Kind of context a process holds, told by the NVIDIA driver libraries it
+/// maps. Processes that map neither the CUDA nor the OpenGL/Vulkan driver,
+/// like monitoring tools going through NVML, are not GPU processes.
+fn process_type(process: &Process) -> Option<ProcessType> {
+    let maps = process.maps().ok()?;
+    let (mut compute, mut graphics) = (false, false);
+    for map in maps {
+        let MMapPath::Path(path) = map.pathname else {
+            continue;
+        };
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        compute |= name.starts_with("libcuda.so");
+        graphics |= name.starts_with("libnvidia-glcore.so")
+            || name.starts_with("libnvidia-eglcore.so")
+            || name.starts_with("libGLX_nvidia.so");
+    }
+    match (compute, graphics) {
+        (true, true) => Some(ProcessType::ComputeGraphics),
+        (true, false) => Some(ProcessType::Compute),
+        (false, true) => Some(ProcessType::Graphics),
+        (false, false) => None,
+    }
+}
@@ -1,3 +1,5 @@
+use chrono::{Local, TimeZone};
+
 pub fn format_memory_size(bytes: u64) -> String {
     const GB: u64 = 1024 * 1024 * 1024;
     const MB: u64 = 1024 * 1024;
@@ -8,3 +10,99 @@ pub fn format_memory_size(bytes: u64) -> String {
         format!("{}MB", bytes / MB)
     }
 }
+
+/// Formats a value that a device may not report, falling back to "N/A".
+pub fn format_or_na<T>(value: Option<T>, format: impl FnOnce(T) -> String) -> String {
+    value.map(format).unwrap_or_else(|| "N/A".to_string())
+}
+
+pub fn format_power(usage: Option<u32>, limit: Option<u32>) -> String {
+    if usage.is_none() && limit.is_none() {
+        return "N/A".to_string();
+    }
+    format!(
+        "{}/{}W",
+        format_or_na(usage, |u| u.to_string()),
+        format_or_na(limit, |l| l.to_string())
+    )
+}
+
+pub fn format_gpu_memory(used: Option<u64>, total: Option<u64>) -> String {
+    format_or_na(used.zip(total), |(used, total)| {
+        format!("{}/{}", format_memory_size(used), format_memory_size(total))
+    })
+}
+
+pub fn format_clock(current: Option<u32>, max: Option<u32>) -> String {
+    match (current, max) {
+        (Some(current), Some(max)) => format!("{}/{}MHz", current, max),
+        (current, _) => format_or_na(current, |c| format!("{}MHz", c)),
+    }
+}
+
+pub fn format_pcie_link(generation: Option<u32>, width: Option<u32>) -> String {
+    format_or_na(generation.zip(width), |(generation, width)| {
+        format!("Gen{} x{}", generation, width)
+    })
+}
+
+/// Formats a throughput given in KB/s, as reported by NVML.
+pub fn format_throughput(kilobytes_per_second: u32) -> String {
+    const MB: f64 = 1024.0;
+    const GB: f64 = 1024.0 * 1024.0;
+
+    let value = kilobytes_per_second as f64;
+    if value >= GB {
+        format!("{:.1}GB/s", value / GB)
+    } else if value >= MB {
+        format!("{:.1}MB/s", value / MB)
+    } else {
+        format!("{}KB/s", kilobytes_per_second)
+    }
+}
+
+pub fn format_pcie_throughput(rx: Option<u32>, tx: Option<u32>) -> String {
+    if rx.is_none() && tx.is_none() {
+        return "N/A".to_string();
+    }
+    format!(
+        "↓{} ↑{}",
+        format_or_na(rx, format_throughput),
+        format_or_na(tx, format_throughput)
+    )
+}
+
+/// Formats a duration like `ps` does for elapsed time: `[[dd-]hh:]mm:ss`.
+pub fn format_duration(duration_ms: u64) -> String {
+    let seconds = duration_ms / 1000;
+    let (days, hours, minutes, seconds) = (
+        seconds / 86400,
+        seconds / 3600 % 24,
+        seconds / 60 % 60,
+        seconds % 60,
+    );
+    if days > 0 {
+        format!("{}-{:02}:{:02}:{:02}", days, hours, minutes, seconds)
+    } else if hours > 0 {
+        format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{:02}:{:02}", minutes, seconds)
+    }
+}
+
+/// Formats a timestamp in local time with a precision suited to the length of
+/// the time range it is shown in.
+pub fn format_timestamp(timestamp_ms: u64, range_ms: u64) -> String {
+    let format = if range_ms <= 3600 * 1000 {
+        "%H:%M:%S"
+    } else if range_ms <= 24 * 3600 * 1000 {
+        "%H:%M"
+    } else {
+        "%m-%d %H:%M"
+    };
+    Local
+        .timestamp_millis_opt(timestamp_ms as i64)
+        .single()
+        .map(|time| time.format(format).to_string())
+        .unwrap_or_default()
+}
@@ -1,2 +1,6 @@
+pub mod container;
+pub mod device_files;
 pub mod formatting;
+pub mod pid_namespace;
+pub mod slurm;
 pub mod system;
@@ -0,0 +1,59 @@
+use procfs::process::Process;
+use std::fs;
+use std::path::PathBuf;
+
+// Inode of the initial PID namespace, which the kernel gives a fixed number
+const INIT_PID_NAMESPACE: &str = "pid:[4026531836]";
+
+/// Where nviwatch runs relative to the host's PID namespace, which the PIDs
+/// NVML reports are from.
+pub struct PidNamespace {
+    // How many namespaces below the one `/proc` was mounted in nviwatch runs,
+    // as when the host's `/proc` is mounted into a container
+    depth: usize,
+    // Target of `/proc/self/ns/pid`, naming nviwatch's namespace
+    namespace: Option<PathBuf>,
+    // In a namespace of its own with a matching `/proc`, as in a container
+    // without `--pid=host`, where host PIDs can't be looked up at all
+    isolated: bool,
+}
+
+impl PidNamespace {
+    pub fn detect() -> Self {
+        let depth = Process::myself()
+            .and_then(|process| process.status())
+            .ok()
+            .and_then(|status| status.nspid)
+            .map_or(0, |nspid| nspid.len().saturating_sub(1));
+        let namespace = fs::read_link("/proc/self/ns/pid").ok();
+        // Without access to the link, assume the common case
+        let in_init_namespace = namespace
+            .as_ref()
+            .is_none_or(|link| link.to_str() == Some(INIT_PID_NAMESPACE));
+        PidNamespace {
+            depth,
+            namespace,
+            isolated: depth == 0 && !in_init_namespace,
+        }
+    }
+
+    /// The `/proc` entry of a PID NVML reports, or `None` if the host's
+    /// processes are not visible from here.
+    pub fn proc_pid(&self, host_pid: u32) -> Option<u32> {
+        (!self.isolated).then_some(host_pid)
+    }
+
+    /// PID of a process in nviwatch's own namespace, from the `NSpid` line of
+    /// its status, or `None` if it runs in another one.
+    pub fn local_pid(&self, process: &Process) -> Option<u32> {
+        if self.depth == 0 {
+            return Some(process.pid as u32);
+        }
+        let namespace = fs::read_link(format!("/proc/{}/ns/pid", process.pid)).ok();
+        if namespace.is_none() || namespace != self.namespace {
+            return None;
+        }
+        let nspid = process.status().ok()?.nspid?;
+        nspid.last().map(|&pid| pid as u32)
+    }
+}
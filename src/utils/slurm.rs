@@ -0,0 +1,156 @@
+use crate::gpu::process::SlurmJob;
+use std::collections::HashMap;
+use std::ffi::OsString;
+
+/// Finds the Slurm job of a process. The cgroup path gives the job and step
+/// of any process, as in `/slurm/uid_1000/job_1234/step_0/task_0` (cgroup v1)
+/// or `/system.slice/slurmstepd.scope/job_1234/step_0/user/task_0` (v2). The
+/// `SLURM_*` environment adds the array task and job name, but can only be
+/// read for processes of the same user unless running as root.
+pub fn find_slurm_job(
+    cgroup_paths: &[String],
+    environ: Option<&HashMap<OsString, OsString>>,
+) -> Option<SlurmJob> {
+    let var = |name: &str| {
+        environ?
+            .get(&OsString::from(name))
+            .and_then(|value| value.to_str())
+            .map(String::from)
+    };
+    let from_cgroup = cgroup_paths.iter().find_map(|path| parse_cgroup(path));
+
+    let job_id = match &from_cgroup {
+        Some((job_id, _)) => *job_id,
+        None => var("SLURM_JOB_ID")?.parse().ok()?,
+    };
+    let step = from_cgroup
+        .and_then(|(_, step)| step)
+        .or_else(|| var("SLURM_STEP_ID"));
+    Some(SlurmJob {
+        job_id,
+        step,
+        array_job_id: var("SLURM_ARRAY_JOB_ID").and_then(|id| id.parse().ok()),
+        array_task_id: var("SLURM_ARRAY_TASK_ID").and_then(|id| id.parse().ok()),
+        name: var("SLURM_JOB_NAME"),
+    })
+}
+
+/// Job ID and step from the `job_<id>` and `step_<step>` components of a
+/// cgroup path.
+fn parse_cgroup(path: &str) -> Option<(u64, Option<String>)> {
+    let mut components = path.split('/');
+    let job_id = components
+        .by_ref()
+        .find_map(|component| component.strip_prefix("job_")?.parse().ok())?;
+    let step = components
+        .next()
+        .and_then(|component| component.strip_prefix("step_"))
+        .map(String::from);
+    Some((job_id, step))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn environ(vars: &[(&str, &str)]) -> HashMap<OsString, OsString> {
+        vars.iter()
+            .map(|&(name, value)| (OsString::from(name), OsString::from(value)))
+            .collect()
+    }
+
+    fn paths(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|path| path.to_string()).collect()
+    }
+
+    #[test]
+    fn job_and_step_from_cgroup_v1() {
+        let job =
+            find_slurm_job(&paths(&["/slurm/uid_1000/job_1234/step_0/task_0"]), None).unwrap();
+        assert_eq!(job.job_id, 1234);
+        assert_eq!(job.step.as_deref(), Some("0"));
+        assert_eq!(job.display_id(), "1234");
+    }
+
+    #[test]
+    fn job_and_step_from_cgroup_v2() {
+        let job = find_slurm_job(
+            &paths(&["/system.slice/slurmstepd.scope/job_1234/step_3/user/task_0"]),
+            None,
+        )
+        .unwrap();
+        assert_eq!(job.job_id, 1234);
+        assert_eq!(job.step.as_deref(), Some("3"));
+    }
+
+    #[test]
+    fn special_steps() {
+        for step in ["batch", "extern", "interactive"] {
+            let path = format!("/system.slice/slurmstepd.scope/job_77/step_{}", step);
+            let job = find_slurm_job(&[path], None).unwrap();
+            assert_eq!(job.job_id, 77);
+            assert_eq!(job.step.as_deref(), Some(step));
+        }
+    }
+
+    #[test]
+    fn job_without_step() {
+        let job = find_slurm_job(&paths(&["/slurm/uid_1000/job_1234"]), None).unwrap();
+        assert_eq!(job.job_id, 1234);
+        assert_eq!(job.step, None);
+    }
+
+    #[test]
+    fn array_task_from_environment() {
+        let environ = environ(&[
+            ("SLURM_JOB_ID", "1240"),
+            ("SLURM_ARRAY_JOB_ID", "1234"),
+            ("SLURM_ARRAY_TASK_ID", "6"),
+            ("SLURM_JOB_NAME", "sweep"),
+        ]);
+        let job = find_slurm_job(
+            &paths(&["/system.slice/slurmstepd.scope/job_1240/step_0/user/task_0"]),
+            Some(&environ),
+        )
+        .unwrap();
+        assert_eq!(job.job_id, 1240);
+        assert_eq!(job.array_job_id, Some(1234));
+        assert_eq!(job.array_task_id, Some(6));
+        assert_eq!(job.name.as_deref(), Some("sweep"));
+        assert_eq!(job.display_id(), "1234_6");
+    }
+
+    #[test]
+    fn environment_fallback_without_slurm_cgroup() {
+        let environ = environ(&[("SLURM_JOB_ID", "99"), ("SLURM_STEP_ID", "2")]);
+        let job = find_slurm_job(
+            &paths(&["/user.slice/user-1000.slice/session-2.scope"]),
+            Some(&environ),
+        )
+        .unwrap();
+        assert_eq!(job.job_id, 99);
+        assert_eq!(job.step.as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn cgroup_wins_over_environment() {
+        // The environment can be inherited from an enclosing allocation
+        let environ = environ(&[("SLURM_JOB_ID", "1"), ("SLURM_STEP_ID", "9")]);
+        let job = find_slurm_job(
+            &paths(&["/slurm/uid_1000/job_1234/step_0/task_0"]),
+            Some(&environ),
+        )
+        .unwrap();
+        assert_eq!(job.job_id, 1234);
+        assert_eq!(job.step.as_deref(), Some("0"));
+    }
+
+    #[test]
+    fn not_a_slurm_process() {
+        let paths = paths(&["/user.slice/user-1000.slice/session-2.scope"]);
+        assert!(find_slurm_job(&paths, None).is_none());
+        assert!(find_slurm_job(&paths, Some(&environ(&[]))).is_none());
+        assert!(find_slurm_job(&paths, Some(&environ(&[("SLURM_JOB_ID", "x")]))).is_none());
+        assert!(parse_cgroup("/docker/job_abc").is_none());
+    }
+}
@@ -1,77 +1,447 @@
-use crate::gpu::process::GpuProcessInfo;
-use crate::AppState;
+use crate::gpu::process::{GpuProcessInfo, Launcher, ProcessType, SlurmJob};
+use crate::session::unix_time_ms;
+use crate::utils::container::{parse_cgroup, ContainerResolver};
+use crate::utils::pid_namespace::PidNamespace;
+use crate::utils::slurm::find_slurm_job;
 use nix::sys::signal::{kill, Signal};
 use nix::unistd::Pid;
 use nix::unistd::{sysconf, SysconfVar};
 use nix::unistd::{Uid, User};
-use procfs::process::Process;
+use procfs::process::{FDTarget, MMapPath, Process, Stat};
+use procfs::ProcessCGroup;
+use std::collections::HashMap;
 use std::fs;
 use std::io::{Error as IoError, ErrorKind};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 
-pub fn get_process_info(pid: u32, used_gpu_memory: u64) -> Option<GpuProcessInfo> {
+// Reading `smaps_rollup` walks every mapping of the process, which is slow
+// for the kernel on CUDA processes with a large address space
+const ROLLUP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// What is known about a process as of one refresh.
+struct TrackedProcess {
+    // Start time since boot in clock ticks, to detect reused PIDs
+    start_time: u64,
+    cpu_ticks: u64,
+    taken_at: Instant,
+    // None until the process has been seen in two refreshes
+    usage: Option<f32>,
+    rollup: Option<MemoryRollup>,
+    rollup_at: Instant,
+    // Read once, as they are fixed for the life of the process
+    launcher: Option<Launcher>,
+    cgroup: Option<String>,
+    slurm_job: Option<SlurmJob>,
+}
+
+/// Keeps per-process state between refreshes: the CPU time to compute usage
+/// from, and the values that are too costly to read on every refresh.
+///
+/// CPU usage is measured over the interval between two refreshes, the way
+/// `top` does: 100% is one fully busy core, so multi-threaded processes can go
+/// above 100%.
+#[derive(Default)]
+pub struct ProcessTracker {
+    previous: HashMap<u32, TrackedProcess>,
+    current: HashMap<u32, TrackedProcess>,
+}
+
+impl ProcessTracker {
+    fn track(&mut self, pid: u32, process: &Process, stat: &Stat) -> &TrackedProcess {
+        // A process running on several GPUs is looked up once per refresh
+        if self
+            .current
+            .get(&pid)
+            .is_some_and(|tracked| tracked.start_time == stat.starttime)
+        {
+            return &self.current[&pid];
+        }
+
+        let clock_ticks = get_clock_ticks_per_second() as f64;
+        let cpu_ticks = stat.utime + stat.stime;
+        let now = Instant::now();
+        let previous = self
+            .previous
+            .remove(&pid)
+            .filter(|previous| previous.start_time == stat.starttime);
+        let usage = match &previous {
+            Some(previous) => {
+                let elapsed = now.duration_since(previous.taken_at).as_secs_f64();
+                let ticks = cpu_ticks.saturating_sub(previous.cpu_ticks) as f64;
+                if elapsed > 0.0 {
+                    Some((ticks / clock_ticks / elapsed * 100.0) as f32)
+                } else {
+                    previous.usage
+                }
+            }
+            // The average since the process started says nothing about its
+            // current load, so there is no usage until the next refresh
+            None => None,
+        };
+        let (rollup, rollup_at) = match &previous {
+            Some(previous) if previous.rollup_at.elapsed() < ROLLUP_INTERVAL => {
+                (previous.rollup, previous.rollup_at)
+            }
+            _ => (read_memory_rollup(process), now),
+        };
+        let (launcher, cgroup, slurm_job) = match previous {
+            Some(previous) => (previous.launcher, previous.cgroup, previous.slurm_job),
+            None => {
+                let cgroups = process.cgroups().map(|c| c.0).unwrap_or_default();
+                let cgroup = cgroup_path(&cgroups);
+                let slurm_job = find_slurm_job(
+                    &cgroups.into_iter().map(|c| c.pathname).collect::<Vec<_>>(),
+                    process.environ().ok().as_ref(),
+                );
+                (find_launcher(stat.ppid), cgroup, slurm_job)
+            }
+        };
+
+        self.current.insert(
+            pid,
+            TrackedProcess {
+                start_time: stat.starttime,
+                cpu_ticks,
+                taken_at: now,
+                usage,
+                rollup,
+                rollup_at,
+                launcher,
+                cgroup,
+                slurm_job,
+            },
+        );
+        &self.current[&pid]
+    }
+
+    /// Ends a refresh. Processes that were not seen in it are forgotten.
+    pub fn finish_refresh(&mut self) {
+        self.previous = std::mem::take(&mut self.current);
+    }
+}
+
+/// Looks up a GPU process by its PID in `/proc`. The process is reported with
+/// its PID in nviwatch's own namespace, or marked foreign if it has none.
+pub fn get_process_info(
+    pid: u32,
+    used_gpu_memory: Option<u64>,
+    process_type: ProcessType,
+    process_tracker: &mut ProcessTracker,
+    container_resolver: &mut ContainerResolver,
+    pid_namespace: &PidNamespace,
+) -> Option<GpuProcessInfo> {
     if let Ok(process) = Process::new(pid as i32) {
         if let Ok(uid) = process.uid() {
-            if let Ok(Some(user)) = User::from_uid(Uid::from_raw(uid)) {
-                let command = process.cmdline().unwrap_or_default().join(" ");
-                let cpu_usage = process
-                    .stat()
-                    .ok()
-                    .and_then(|stat| {
-                        let total_time = stat.utime + stat.stime;
-                        let clock_ticks = get_clock_ticks_per_second();
-                        let uptime = get_system_uptime();
-                        Some((total_time as f64 / clock_ticks as f64 / uptime * 100.0) as f32)
-                    })
-                    .unwrap_or(0.0);
-                let memory_usage = process.stat().ok().map(|stat| stat.rss * 4096).unwrap_or(0);
-
-                return Some(GpuProcessInfo {
-                    pid,
-                    used_gpu_memory,
-                    username: user.name,
-                    command,
-                    cpu_usage,
-                    memory_usage,
-                });
-            }
+            // Users missing from a container's passwd file show as their UID
+            let username = match User::from_uid(Uid::from_raw(uid)) {
+                Ok(Some(user)) => user.name,
+                _ => uid.to_string(),
+            };
+            let local_pid = pid_namespace.local_pid(&process);
+            let command = process.cmdline().unwrap_or_default().join(" ");
+            let stat = process.stat().ok();
+            let tracked = stat
+                .as_ref()
+                .map(|stat| process_tracker.track(pid, &process, stat));
+            let cpu_usage = tracked.and_then(|tracked| tracked.usage);
+            let rollup = tracked.and_then(|tracked| tracked.rollup);
+            let memory_usage = stat
+                .as_ref()
+                .map(|stat| stat.rss * procfs::page_size())
+                .unwrap_or(0);
+            let cgroup = tracked.and_then(|tracked| tracked.cgroup.clone());
+            let container = cgroup
+                .as_deref()
+                .and_then(parse_cgroup)
+                .map(|container| container_resolver.resolve(container));
+
+            return Some(GpuProcessInfo {
+                pid: local_pid.unwrap_or(pid),
+                proc_pid: Some(pid),
+                used_gpu_memory,
+                process_type: Some(process_type),
+                utilization: None,
+                username,
+                command,
+                cpu_usage,
+                memory_usage,
+                memory_pss: rollup.map(|r| r.pss),
+                memory_uss: rollup.map(|r| r.uss),
+                memory_swap: rollup.map(|r| r.swap),
+                ppid: stat.as_ref().map(|stat| stat.ppid as u32),
+                threads: stat.as_ref().map(|stat| stat.num_threads as u32),
+                state: stat.as_ref().map(|stat| stat.state),
+                nice: stat.as_ref().map(|stat| stat.nice as i32),
+                start_time_ms: stat.as_ref().map(|stat| start_time_ms(stat.starttime)),
+                cgroup,
+                launcher: tracked.and_then(|tracked| tracked.launcher.clone()),
+                container,
+                slurm_job: tracked.and_then(|tracked| tracked.slurm_job.clone()),
+                foreign: local_pid.is_none(),
+            });
         }
     }
     None
 }
-pub fn kill_selected_process(app_state: &AppState) -> Result<(), Box<dyn std::error::Error>> {
-    let mut all_processes = Vec::new();
-    for gpu_info in &app_state.gpu_infos {
-        all_processes.extend(gpu_info.processes.iter());
+
+/// Programs that start the ranks of a distributed job.
+const LAUNCHERS: [&str; 9] = [
+    "torchrun",
+    "torch.distributed.run",
+    "torch.distributed.launch",
+    "deepspeed",
+    "accelerate",
+    "mpirun",
+    "mpiexec",
+    "horovodrun",
+    "srun",
+];
+
+// Bounds the walk up the process tree
+const MAX_ANCESTORS: usize = 32;
+
+/// Finds the process a GPU process with parent `ppid` is grouped under. This
+/// is the outermost launcher among its ancestors, so MPI ranks end up under
+/// `mpirun` rather than the per-node daemon, and otherwise the parent itself.
+fn find_launcher(ppid: i32) -> Option<Launcher> {
+    let mut launcher = None;
+    let mut parent = None;
+    let mut pid = ppid;
+    for _ in 0..MAX_ANCESTORS {
+        // Processes started by init or the kernel have no meaningful launcher
+        if pid <= 1 {
+            break;
+        }
+        let Ok(process) = Process::new(pid) else {
+            break;
+        };
+        let cmdline = process.cmdline().unwrap_or_default();
+        let ancestor = Launcher {
+            pid: pid as u32,
+            command: cmdline.join(" "),
+        };
+        if is_launcher(&cmdline) {
+            launcher = Some(ancestor);
+        } else if parent.is_none() {
+            parent = Some(ancestor);
+        }
+        match process.stat() {
+            Ok(stat) => pid = stat.ppid,
+            Err(_) => break,
+        }
+    }
+    launcher.or(parent)
+}
+
+/// Whether a command line runs one of `LAUNCHERS`, directly or through an
+/// interpreter such as `python -m torch.distributed.run`.
+fn is_launcher(cmdline: &[String]) -> bool {
+    cmdline.iter().take(3).any(|arg| {
+        let name = arg.rsplit('/').next().unwrap_or(arg);
+        LAUNCHERS.contains(&name)
+    })
+}
+
+/// Converts a start time in clock ticks since boot to milliseconds since the
+/// Unix epoch.
+fn start_time_ms(start_ticks: u64) -> u64 {
+    // Taken once so a process keeps exactly the same start time across
+    // refreshes, which is how a reused PID is told apart
+    static BOOT_TIME: OnceLock<f64> = OnceLock::new();
+    let since_boot = start_ticks as f64 / get_clock_ticks_per_second() as f64;
+    let boot_time = *BOOT_TIME.get_or_init(|| unix_time_ms() as f64 / 1000.0 - get_system_uptime());
+    ((boot_time + since_boot) * 1000.0) as u64
+}
+
+/// Start time of the process with `/proc` entry `proc_pid` as it is now, in
+/// the form of `GpuProcessInfo::start_time_ms`.
+pub fn read_start_time_ms(proc_pid: u32) -> Option<u64> {
+    let stat = Process::new(proc_pid as i32).ok()?.stat().ok()?;
+    Some(start_time_ms(stat.starttime))
+}
+
+/// Path of the process in the cgroup hierarchy. The unified (v2) hierarchy is
+/// preferred; on v1-only systems the first controller's path is used.
+fn cgroup_path(cgroups: &[ProcessCGroup]) -> Option<String> {
+    cgroups
+        .iter()
+        .find(|cgroup| cgroup.hierarchy == 0)
+        .or_else(|| cgroups.first())
+        .map(|cgroup| cgroup.pathname.clone())
+}
+
+/// What the detail pane shows about a process beyond its sampled fields.
+/// Read from `/proc` when the pane opens; each part is empty when it can't be
+/// read, which for other users' processes usually means everything but the
+/// command line.
+#[derive(Default)]
+pub struct ProcessDetails {
+    pub exe: Option<String>,
+    pub cwd: Option<String>,
+    // GPU related environment variables, sorted by name
+    pub environment: Vec<(String, String)>,
+    // Open `/dev/nvidia*` files with their descriptor numbers
+    pub device_handles: Vec<(i32, String)>,
+    // Mapped CUDA, cuDNN and NCCL libraries with the version in their file name
+    pub libraries: Vec<(String, Option<String>)>,
+}
+
+// Variables that affect how a process uses the GPUs
+const GPU_ENVIRONMENT: [&str; 3] = [
+    "CUDA_VISIBLE_DEVICES",
+    "NVIDIA_VISIBLE_DEVICES",
+    "OMP_NUM_THREADS",
+];
+const GPU_ENVIRONMENT_PREFIXES: [&str; 2] = ["NCCL_", "CUDA_"];
+
+// Libraries whose versions are listed in the detail pane
+const GPU_LIBRARIES: [&str; 5] = ["libcuda", "libcudart", "libcudnn", "libcublas", "libnccl"];
+
+/// Reads the details of the process with `/proc` entry `proc_pid`.
+pub fn read_process_details(proc_pid: u32) -> ProcessDetails {
+    let Ok(process) = Process::new(proc_pid as i32) else {
+        return ProcessDetails::default();
+    };
+    let path = |path: std::path::PathBuf| path.to_string_lossy().into_owned();
+
+    let mut environment: Vec<(String, String)> = process
+        .environ()
+        .map(|environ| {
+            environ
+                .into_iter()
+                .map(|(name, value)| {
+                    (
+                        name.to_string_lossy().into_owned(),
+                        value.to_string_lossy().into_owned(),
+                    )
+                })
+                .filter(|(name, _)| {
+                    GPU_ENVIRONMENT.contains(&name.as_str())
+                        || GPU_ENVIRONMENT_PREFIXES
+                            .iter()
+                            .any(|prefix| name.starts_with(prefix))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    environment.sort();
+
+    let mut device_handles: Vec<(i32, String)> = process
+        .fd()
+        .map(|fds| {
+            fds.filter_map(Result::ok)
+                .filter_map(|fd| match fd.target {
+                    FDTarget::Path(target) if target.starts_with("/dev/nvidia") => {
+                        Some((fd.fd, path(target)))
+                    }
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    device_handles.sort();
+
+    let mut libraries: Vec<(String, Option<String>)> = Vec::new();
+    for map in process.maps().map(|maps| maps.0).unwrap_or_default() {
+        let MMapPath::Path(file) = map.pathname else {
+            continue;
+        };
+        let Some(library) = file
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(parse_library_name)
+        else {
+            continue;
+        };
+        if GPU_LIBRARIES.contains(&library.0.as_str()) && !libraries.contains(&library) {
+            libraries.push(library);
+        }
+    }
+    libraries.sort();
+
+    ProcessDetails {
+        exe: process.exe().ok().map(path),
+        cwd: process.cwd().ok().map(path),
+        environment,
+        device_handles,
+        libraries,
     }
+}
 
-    // Sort processes by GPU memory usage (descending) to match the UI
-    all_processes.sort_by(|a, b| b.used_gpu_memory.cmp(&a.used_gpu_memory));
-
-    if app_state.selected_process < all_processes.len() {
-        let selected_process = &all_processes[app_state.selected_process];
-        let pid = selected_process.pid;
-        match kill(Pid::from_raw(pid as i32), Signal::SIGTERM) {
-            Ok(_) => Ok(()),
-            Err(nix::Error::EPERM) => Err(Box::new(IoError::new(
-                ErrorKind::PermissionDenied,
-                format!(
-                    "Permission denied to terminate process {} ({})",
-                    pid, selected_process.command
-                ),
-            ))),
-            Err(e) => Err(Box::new(IoError::new(
-                ErrorKind::Other,
-                format!(
-                    "Failed to terminate process {} ({}): {}",
-                    pid, selected_process.command, e
-                ),
-            ))),
+/// Splits a library file name like `libcudart.so.12.1.105` into its name and
+/// version. Wheels bundle libraries with a hash in the name, as in
+/// `libcudart-9335f6a2.so.12`, which is left out. cuDNN's sub-libraries such as
+/// `libcudnn_ops_infer` count as `libcudnn`. Libraries replaced on disk since
+/// they were mapped, e.g. by a driver upgrade, keep the ` (deleted)` suffix the
+/// kernel adds in `/proc/<pid>/maps`, which is dropped.
+fn parse_library_name(file_name: &str) -> Option<(String, Option<String>)> {
+    let file_name = file_name.strip_suffix(" (deleted)").unwrap_or(file_name);
+    // `.so` must end the name or start the version, unlike in `.socket`
+    let (name, version) = file_name
+        .match_indices(".so")
+        .map(|(index, _)| (&file_name[..index], &file_name[index + 3..]))
+        .find(|(_, version)| version.is_empty() || version.starts_with('.'))?;
+    let name = match name.rsplit_once('-') {
+        Some((base, hash)) if hash.len() == 8 && hash.bytes().all(|b| b.is_ascii_hexdigit()) => {
+            base
         }
-    } else {
-        Err(Box::new(IoError::new(
-            ErrorKind::NotFound,
-            "Selected process not found",
-        )))
+        _ => name,
+    };
+    let name = match name.split_once('_') {
+        Some(("libcudnn", _)) => "libcudnn",
+        _ => name,
+    };
+    let version = version.strip_prefix('.').filter(|v| !v.is_empty());
+    Some((name.to_string(), version.map(String::from)))
+}
+
+#[derive(Clone, Copy)]
+struct MemoryRollup {
+    pss: u64,
+    uss: u64,
+    swap: u64,
+}
+
+/// Reads PSS, USS and swap from `/proc/<pid>/smaps_rollup`. Shared pages such
+/// as CUDA libraries are split between the processes mapping them in PSS and
+/// left out of USS, so neither double counts across training ranks.
+fn read_memory_rollup(process: &Process) -> Option<MemoryRollup> {
+    let rollup = process.smaps_rollup().ok()?;
+    let map = &rollup.memory_map_rollup.iter().next()?.extension.map;
+    let value = |key: &str| map.get(key).copied().unwrap_or(0);
+    Some(MemoryRollup {
+        pss: value("Pss"),
+        uss: value("Private_Clean") + value("Private_Dirty"),
+        swap: value("Swap"),
+    })
+}
+
+/// Sends `signal` to a GPU process. Errors name the process, and a missing
+/// permission is reported as `PermissionDenied`.
+pub fn send_signal(
+    pid: u32,
+    command: &str,
+    signal: Signal,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match kill(Pid::from_raw(pid as i32), signal) {
+        Ok(_) => Ok(()),
+        Err(nix::Error::EPERM) => Err(Box::new(IoError::new(
+            ErrorKind::PermissionDenied,
+            format!(
+                "Permission denied to send {} to process {} ({})",
+                signal.as_str(),
+                pid,
+                command
+            ),
+        ))),
+        Err(e) => Err(Box::new(IoError::other(format!(
+            "Failed to send {} to process {} ({}): {}",
+            signal.as_str(),
+            pid,
+            command,
+            e
+        )))),
     }
 }
 
@@ -89,3 +459,76 @@ pub fn get_system_uptime() -> f64 {
         .and_then(|uptime_str| uptime_str.parse().ok())
         .unwrap_or(0.0)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library(name: &str, version: Option<&str>) -> Option<(String, Option<String>)> {
+        Some((name.to_string(), version.map(String::from)))
+    }
+
+    #[test]
+    fn library_name_and_version() {
+        assert_eq!(
+            parse_library_name("libcuda.so.535.104.05"),
+            library("libcuda", Some("535.104.05"))
+        );
+        assert_eq!(
+            parse_library_name("libcuda.so.1"),
+            library("libcuda", Some("1"))
+        );
+        assert_eq!(
+            parse_library_name("libnvidia-ml.so.1"),
+            library("libnvidia-ml", Some("1"))
+        );
+        assert_eq!(
+            parse_library_name("libtorch_cuda.so"),
+            library("libtorch_cuda", None)
+        );
+    }
+
+    #[test]
+    fn wheel_hash_and_cudnn_sub_library_are_left_out() {
+        assert_eq!(
+            parse_library_name("libcudart-9335f6a2.so.12"),
+            library("libcudart", Some("12"))
+        );
+        assert_eq!(
+            parse_library_name("libcudnn_ops_infer.so.8.9.2"),
+            library("libcudnn", Some("8.9.2"))
+        );
+        // Only an 8 digit hex suffix is a hash
+        assert_eq!(
+            parse_library_name("libnccl-net.so"),
+            library("libnccl-net", None)
+        );
+    }
+
+    #[test]
+    fn deleted_library_keeps_its_version() {
+        assert_eq!(
+            parse_library_name("libcuda.so.535.104.05 (deleted)"),
+            library("libcuda", Some("535.104.05"))
+        );
+        assert_eq!(
+            parse_library_name("libcudart-9335f6a2.so.12 (deleted)"),
+            library("libcudart", Some("12"))
+        );
+    }
+
+    #[test]
+    fn other_mapped_files_are_not_libraries() {
+        assert_eq!(parse_library_name("python3.10"), None);
+        assert_eq!(parse_library_name("nvidia-persistenced.socket"), None);
+        assert_eq!(parse_library_name("locale-archive"), None);
+        assert_eq!(
+            parse_library_name("_C.cpython-310-x86_64-linux-gnu.so"),
+            library("_C.cpython-310-x86_64-linux-gnu", None)
+        );
+        assert_eq!(
+            parse_library_name("libfoo.solver.so.2"),
+            library("libfoo.solver", Some("2"))
+        );
+    }
+}